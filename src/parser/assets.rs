@@ -0,0 +1,83 @@
+use crate::types::{AssetKind, AssetReference};
+use crate::utils::UrlKind;
+use std::cell::RefCell;
+
+thread_local! {
+    static SINK: RefCell<Option<Vec<AssetReference>>> = const { RefCell::new(None) };
+}
+
+/// Extensions of pages, which the build renders rather than copies.
+const PAGE_EXTENSIONS: &[&str] = &["norg", "html", "htm"];
+
+/// The URL to emit for `url`. While assets are captured with
+/// `asset_placeholders` on, a bare relative path to a file becomes a
+/// placeholder (keeping any `?query` or `#fragment`) and is recorded; anything
+/// else — pages, anchors, rooted and remote URLs — comes back unchanged.
+pub fn reference(url: &str, kind: AssetKind) -> String {
+    if !crate::options::with(|options| options.asset_placeholders.unwrap_or(false)) {
+        return url.to_string();
+    }
+    let split = url.find(['?', '#']).unwrap_or(url.len());
+    let (path, suffix) = url.split_at(split);
+    if !is_file_path(path) {
+        return url.to_string();
+    }
+
+    SINK.with(|sink| match sink.borrow_mut().as_mut() {
+        Some(assets) => {
+            let placeholder = format!("__NORG_ASSET_{}__", assets.len());
+            let path = if path.starts_with("./") || path.starts_with("../") {
+                path.to_string()
+            } else {
+                format!("./{path}")
+            };
+            assets.push(AssetReference {
+                kind,
+                path,
+                placeholder: placeholder.clone(),
+                part: 0,
+            });
+            format!("{placeholder}{suffix}")
+        }
+        None => url.to_string(),
+    })
+}
+
+fn is_file_path(path: &str) -> bool {
+    if path.is_empty() || path.starts_with('/') || !UrlKind::of(path).is_site_relative() {
+        return false;
+    }
+    let name = path.rsplit('/').next().unwrap_or_default();
+    name.rsplit_once('.').is_some_and(|(stem, extension)| {
+        !stem.is_empty()
+            && !PAGE_EXTENSIONS
+                .iter()
+                .any(|page| page.eq_ignore_ascii_case(extension))
+    })
+}
+
+/// Collects the assets referenced while `run` renders. Their `part` is left
+/// for the caller, which has the finished parts.
+pub fn capture<T>(run: impl FnOnce() -> T) -> (T, Vec<AssetReference>) {
+    let previous = SINK.with(|sink| sink.replace(Some(Vec::new())));
+    let value = run();
+    let assets = SINK.with(|sink| sink.replace(previous)).unwrap_or_default();
+    (value, assets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_relative_file_paths_are_assets() {
+        assert!(is_file_path("report.pdf"));
+        assert!(is_file_path("../img/a.PNG"));
+        assert!(!is_file_path("notes.norg"));
+        assert!(!is_file_path("page.HTML"));
+        assert!(!is_file_path("docs/"));
+        assert!(!is_file_path(".env"));
+        assert!(!is_file_path("/logo.png"));
+        assert!(!is_file_path("https://example.com/a.png"));
+    }
+}
@@ -0,0 +1,263 @@
+use super::{IMAGE_OPTIONS, is_tangle_tag, quoted_value, warn_carryover_ignored};
+use crate::utils::is_plain_id;
+use htmlescape::encode_minimal;
+use rust_norg::NorgAST;
+use std::fmt::Write;
+
+/// What `+id`, `+class` and `+attr` carryovers set on the block they annotate.
+#[derive(Default)]
+pub struct BlockAttrs {
+    id: Option<String>,
+    classes: Vec<String>,
+    attributes: Vec<(String, String)>,
+    /// Set by `+collapsed`; only headings honour it.
+    pub collapsed: bool,
+    /// `+width 800`, `+caption …` and the other image options, as `key=value`
+    /// parameters for the image tag.
+    image_options: Vec<String>,
+}
+
+impl BlockAttrs {
+    pub fn is_empty(&self) -> bool {
+        self.id.is_none() && self.classes.is_empty() && self.attributes.is_empty()
+    }
+
+    /// The image tag's parameters with the image option carryovers appended.
+    pub fn image_parameters(&self, parameters: &[String]) -> Vec<String> {
+        parameters
+            .iter()
+            .chain(&self.image_options)
+            .cloned()
+            .collect()
+    }
+
+    /// Reports image option carryovers on anything but an image.
+    pub fn warn_image_options_ignored(&self) {
+        if !self.image_options.is_empty() {
+            crate::diagnostics::warn("image option carryovers apply only to images — ignored");
+        }
+    }
+
+    /// Reports a `+collapsed` on anything but a heading.
+    pub fn warn_collapsed_ignored(&self) {
+        if self.collapsed {
+            crate::diagnostics::warn(
+                "carryover tag 'collapsed' applies only to headings — ignored",
+            );
+        }
+    }
+
+    /// Records one carryover tag, reporting it if it sets nothing.
+    fn add(&mut self, name: &[String], parameters: &[String]) {
+        match name {
+            [tag] if tag == "id" => match carryover_id(name, parameters) {
+                Some(id) => self.id = Some(id),
+                None if parameters.iter().all(String::is_empty) => {
+                    crate::diagnostics::warn("carryover tag 'id' has no value — ignored")
+                }
+                None => crate::diagnostics::warn(format!(
+                    "carryover id '{}' may only contain letters, digits, '-' and '_' — ignored",
+                    parameters.join(" ")
+                )),
+            },
+            [tag] if tag == "collapsed" => self.collapsed = true,
+            _ if is_tangle_tag(name) => {}
+            [tag] if IMAGE_OPTIONS.contains(&tag.as_str()) => self
+                .image_options
+                .push(format!("{tag}={}", parameters.join(" "))),
+            [tag] if tag == "class" => self
+                .classes
+                .extend(parameters.iter().filter(|class| !class.is_empty()).cloned()),
+            [tag] if tag == "attr" => {
+                let mut parameters = parameters.iter();
+                while let Some(parameter) = parameters.next() {
+                    match parameter.split_once('=') {
+                        Some((key, value)) if is_settable_attribute(key) => self.attributes.push((
+                            key.to_ascii_lowercase(),
+                            quoted_value(value, &mut parameters),
+                        )),
+                        _ => crate::diagnostics::warn(format!(
+                            "carryover attribute '{parameter}' is not a data-*, aria-*, title, \
+                             lang, dir or role attribute — ignored"
+                        )),
+                    }
+                }
+            }
+            _ => warn_carryover_ignored(name),
+        }
+    }
+
+    /// The attributes as opening-tag markup (` id="…" class="…" data-x="…"`).
+    /// Headings pass `with_id: false`: their id comes from `DocumentIds`, which
+    /// already resolved the `+id`.
+    pub fn markup(&self, with_id: bool) -> String {
+        let mut out = String::new();
+        if with_id && let Some(id) = &self.id {
+            let _ = write!(out, r#" id="{}""#, encode_minimal(id));
+        }
+        if !self.classes.is_empty() {
+            let _ = write!(out, r#" class="{}""#, self.class_list());
+        }
+        self.write_attributes(&mut out);
+        out
+    }
+
+    /// Adds the attributes to the first opening tag in `html`, merging the
+    /// classes into a `class` the renderer already set.
+    pub fn inject(&self, html: &mut String) {
+        if self.is_empty() {
+            return;
+        }
+        let Some(open) = html.find('<') else {
+            return;
+        };
+        let name_end = html[open + 1..]
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .map_or(html.len(), |i| open + 1 + i);
+        let tag_end = html[name_end..]
+            .find('>')
+            .map_or(html.len(), |i| name_end + i);
+        let existing_class = html[name_end..tag_end]
+            .find(r#" class=""#)
+            .map(|i| name_end + i + r#" class=""#.len());
+
+        let mut markup = String::new();
+        if let Some(id) = &self.id {
+            let _ = write!(markup, r#" id="{}""#, encode_minimal(id));
+        }
+        match existing_class {
+            // Inserted first: it sits after `name_end`, so it doesn't shift it.
+            Some(at) if !self.classes.is_empty() => {
+                html.insert_str(at, &format!("{} ", self.class_list()));
+            }
+            _ if !self.classes.is_empty() => {
+                let _ = write!(markup, r#" class="{}""#, self.class_list());
+            }
+            _ => {}
+        }
+        self.write_attributes(&mut markup);
+        html.insert_str(name_end, &markup);
+    }
+
+    fn class_list(&self) -> String {
+        encode_minimal(&self.classes.join(" "))
+    }
+
+    fn write_attributes(&self, out: &mut String) {
+        for (key, value) in &self.attributes {
+            let _ = write!(out, r#" {key}="{}""#, encode_minimal(value));
+        }
+    }
+}
+
+/// Splits the carryover chain on `node` into the attributes it sets and the
+/// annotated node, reporting carryovers that set nothing. Comment carryovers
+/// never get here — `comment_scope` claims those first.
+pub fn block_attrs(mut node: &NorgAST) -> (BlockAttrs, &NorgAST) {
+    let mut attrs = BlockAttrs::default();
+    while let NorgAST::CarryoverTag {
+        name,
+        parameters,
+        next_object,
+        ..
+    } = node
+    {
+        attrs.add(name, parameters);
+        node = next_object;
+    }
+    (attrs, node)
+}
+
+/// The id a single carryover tag sets, if it's a valid `+id`. An id can't
+/// contain whitespace, so extra words are joined with dashes; anything beyond
+/// ASCII letters, digits, `-` and `_` is refused, as the id lands unescaped in
+/// `href`s and the TOC.
+pub fn carryover_id(name: &[String], parameters: &[String]) -> Option<String> {
+    let id = match name {
+        [tag] if tag == "id" => parameters.join("-"),
+        _ => return None,
+    };
+    (!id.is_empty() && is_plain_id(&id)).then_some(id)
+}
+
+/// `+attr` may only set attributes with no behaviour of their own: anything
+/// else (event handlers, `style`, URLs, `id`/`class`) has a dedicated tag or
+/// no business in a document.
+fn is_settable_attribute(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    let well_formed = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    well_formed
+        && (key.starts_with("data-")
+            || key.starts_with("aria-")
+            || matches!(key.as_str(), "title" | "lang" | "dir" | "role"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attrs(tags: &[(&str, &[&str])]) -> BlockAttrs {
+        let mut attrs = BlockAttrs::default();
+        for (name, parameters) in tags {
+            let parameters: Vec<String> = parameters.iter().map(|p| p.to_string()).collect();
+            attrs.add(&[name.to_string()], &parameters);
+        }
+        attrs
+    }
+
+    #[test]
+    fn inject_merges_into_an_existing_class() {
+        let attrs = attrs(&[
+            ("id", &["hero"]),
+            ("class", &["wide", "callout"]),
+            ("attr", &["data-track=hero"]),
+        ]);
+        let mut html = r#"<pre class="arborium lang-rust"><code>x</code></pre>"#.to_string();
+        attrs.inject(&mut html);
+        assert_eq!(
+            html,
+            r#"<pre id="hero" data-track="hero" class="wide callout arborium lang-rust"><code>x</code></pre>"#
+        );
+
+        let mut html = r#"<img src="./a.png" alt="" />"#.to_string();
+        attrs.inject(&mut html);
+        assert!(
+            html.starts_with(r#"<img id="hero" class="wide callout" data-track="hero" src"#),
+            "{html}"
+        );
+    }
+
+    #[test]
+    fn behavioural_attributes_are_refused() {
+        let (attrs, diagnostics) = crate::diagnostics::capture(|| {
+            attrs(&[(
+                "attr",
+                &["onclick=alert(1)", "style=color:red", "data-ok=1"],
+            )])
+        });
+        assert_eq!(attrs.markup(true), r#" data-ok="1""#);
+        assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+    }
+
+    #[test]
+    fn quoted_attribute_values_keep_their_spaces() {
+        let attrs = attrs(&[("attr", &[r#"title="a"#, r#"b""#, "data-n=1"])]);
+        assert_eq!(attrs.markup(true), r#" title="a b" data-n="1""#);
+    }
+
+    #[test]
+    fn ids_outside_the_safe_set_are_refused() {
+        let (attrs, diagnostics) = crate::diagnostics::capture(|| {
+            attrs(&[("id", &[r#"a"><img/src=x/onerror=alert(1)>"#])])
+        });
+        assert_eq!(attrs.markup(true), "");
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert_eq!(
+            carryover_id(&["id".to_string()], &["getting".into(), "started_2".into()]).as_deref(),
+            Some("getting-started_2")
+        );
+    }
+}
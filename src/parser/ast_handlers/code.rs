@@ -0,0 +1,513 @@
+use super::quoted_value;
+use arborium::advanced::{Span, spans_to_html};
+use arborium::{Highlighter, HtmlFormat};
+use htmlescape::encode_minimal;
+use std::borrow::Cow;
+use std::ops::RangeInclusive;
+use textwrap::dedent;
+
+/// Options after an `@code` block's language: `linenos` (or `linenos=N` to
+/// start at `N`), a `{2,5-7}` set of lines to highlight, counted from the
+/// block's first line whatever the numbering, and a `title=` or `file=`
+/// caption. An empty block with `file=` includes that file, cut down to
+/// `region=` and then `lines=` when given.
+#[derive(Default)]
+pub struct CodeOptions {
+    /// The first line's number, when lines are numbered.
+    pub line_numbers: Option<u32>,
+    highlighted: Vec<RangeInclusive<usize>>,
+    title: Option<String>,
+    file: Option<String>,
+    /// Lines of the included file (or region), counted from 1.
+    lines: Option<RangeInclusive<usize>>,
+    region: Option<String>,
+    /// Per-line change markers of a `@code diff <lang>` block.
+    changes: Vec<LineChange>,
+}
+
+/// What a line of a diff block does, from the marker stripped off it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LineChange {
+    Added,
+    Removed,
+    Context,
+}
+
+impl CodeOptions {
+    /// Reads the options, reporting unknown ones and malformed values. A
+    /// highlight set may span parameters (`{2, 5-7}`).
+    pub fn parse(parameters: &[String]) -> Self {
+        let mut options = Self::default();
+        let mut parameters = parameters.iter();
+        while let Some(parameter) = parameters.next() {
+            if parameter.starts_with('{') {
+                let mut set = parameter.clone();
+                while !set.ends_with('}') {
+                    let Some(next) = parameters.next() else {
+                        break;
+                    };
+                    set.push_str(next);
+                }
+                options.highlighted.extend(line_ranges(&set));
+                continue;
+            }
+            match parameter.split_once('=') {
+                None if parameter == "linenos" => options.line_numbers = Some(1),
+                Some(("title", title)) => {
+                    options.title = Some(quoted_value(title, &mut parameters));
+                }
+                Some(("file", file)) => options.file = Some(quoted_value(file, &mut parameters)),
+                Some(("lines", lines)) => match line_range(lines) {
+                    Some(range) => options.lines = Some(range),
+                    None => crate::diagnostics::warn(format!(
+                        "invalid value '{lines}' for code option 'lines' — ignored"
+                    )),
+                },
+                Some(("region", region)) => options.region = Some(region.to_string()),
+                Some(("linenos", start)) => match start.parse() {
+                    Ok(start) => options.line_numbers = Some(start),
+                    Err(_) => crate::diagnostics::warn(format!(
+                        "invalid value '{start}' for code option 'linenos' — ignored"
+                    )),
+                },
+                _ => {
+                    crate::diagnostics::warn(format!("unknown code option '{parameter}' — ignored"))
+                }
+            }
+        }
+        options
+    }
+
+    /// The body of a block with `file=` and none of its own: the file's text,
+    /// cut to its region and lines. `None`, reported, when the file or the
+    /// part asked for can't be found.
+    pub fn include(&self, content: &str) -> Option<String> {
+        let file = match &self.file {
+            Some(file) if content.trim().is_empty() => file,
+            _ => {
+                if self.lines.is_some() || self.region.is_some() {
+                    crate::diagnostics::warn(
+                        "lines= and region= only apply to an empty code block with file= — ignored",
+                    );
+                }
+                return None;
+            }
+        };
+        let text = crate::includes::read(file)?;
+        let text = match &self.region {
+            Some(name) => region(&text, name, file)?,
+            None => text,
+        };
+        match &self.lines {
+            Some(range) => select_lines(&text, range, file),
+            None => Some(text),
+        }
+    }
+
+    /// Reports highlighted lines past the end of a block of `lines` lines.
+    pub fn check_highlighted(&self, lines: usize) {
+        if let Some(range) = self.highlighted.iter().find(|range| *range.end() > lines) {
+            crate::diagnostics::warn(format!(
+                "highlighted line {} is past the end of a {lines}-line code block",
+                range.end()
+            ));
+        }
+    }
+
+    /// Classes for the `<pre>` beyond the highlighter's own, each after a
+    /// space.
+    fn pre_class(&self) -> String {
+        let mut class = String::new();
+        if self.line_numbers.is_some() {
+            class.push_str(" line-numbers");
+        }
+        if !self.changes.is_empty() {
+            class.push_str(" diff");
+        }
+        class
+    }
+
+    /// The caption above the block: the title, or else the file name as code.
+    pub fn caption(&self) -> Option<String> {
+        match (&self.title, &self.file) {
+            (Some(title), _) => Some(format!(
+                r#"<figcaption class="code-title">{}</figcaption>"#,
+                encode_minimal(title)
+            )),
+            (None, Some(file)) => Some(format!(
+                r#"<figcaption class="code-title"><code>{}</code></figcaption>"#,
+                encode_minimal(file)
+            )),
+            (None, None) => None,
+        }
+    }
+
+    /// The opening `<span>` of the line at `index` (0-based).
+    pub fn line_open(&self, index: usize) -> String {
+        let highlighted = self
+            .highlighted
+            .iter()
+            .any(|range| range.contains(&(index + 1)));
+        let mut class = String::from("line");
+        if highlighted {
+            class.push_str(" highlighted");
+        }
+        match self.changes.get(index) {
+            Some(LineChange::Added) => class.push_str(" line-added"),
+            Some(LineChange::Removed) => class.push_str(" line-removed"),
+            Some(LineChange::Context) | None => {}
+        }
+        match self.line_numbers {
+            Some(start) => {
+                let number = u64::from(start) + index as u64;
+                format!(r#"<span class="{class}" data-line="{number}">"#)
+            }
+            None => format!(r#"<span class="{class}">"#),
+        }
+    }
+}
+
+/// A `<pre>` of the code, highlighted when arborium knows the language
+/// (`parameters[0]`), one `<span class="line">` per line either way, in a
+/// `<figure>` when captioned. `@code diff rust` highlights the lines as Rust
+/// once their `+`/`-`/space markers are stripped, marking each line's change
+/// as a class.
+pub fn render_code(parameters: &[String], content: &str, highlighter: &mut Highlighter) -> String {
+    let (lang, diff, rest) = split_parameters(parameters);
+    let mut options = CodeOptions::parse(rest);
+    let included = options.include(content);
+    let dedented = dedent(included.as_deref().unwrap_or(content));
+    // Trim the trailing newline once, up front, so the highlight and fallback
+    // paths see identical input and emit the same number of
+    // `<span class="line">` rows. (Highlighting the untrimmed string but the
+    // fallback the trimmed one could yield a different line count between
+    // languages arborium does and doesn't support.)
+    let source = dedented.trim_end_matches('\n');
+    let code = if diff {
+        let (code, changes) = strip_diff(source);
+        options.changes = changes;
+        Cow::Owned(code)
+    } else {
+        Cow::Borrowed(source)
+    };
+    options.check_highlighted(code.split('\n').count());
+    let extra_class = options.pre_class();
+    // The unhighlighted text as written, for copy buttons.
+    let data_code = if crate::options::with(|o| o.copy_code.unwrap_or(false)) {
+        format!(r#" data-code="{}""#, encode_minimal(source))
+    } else {
+        String::new()
+    };
+    let pre = match highlighter.highlight_spans(lang, &code) {
+        Ok(spans) => format!(
+            r#"<pre class="arborium lang-{}{extra_class}"{data_code}><code>{}</code></pre>"#,
+            encode_minimal(lang),
+            highlight_lines(&code, spans, &options)
+        ),
+        Err(_) => format!(
+            r#"<pre{}{data_code}><code>{}</code></pre>"#,
+            match extra_class.trim_start() {
+                "" => String::new(),
+                class => format!(r#" class="{class}""#),
+            },
+            wrap_plain_lines(&encode_minimal(&code), &options)
+        ),
+    };
+    match options.caption() {
+        Some(caption) => format!(r#"<figure class="code-block">{caption}{pre}</figure>"#),
+        None => pre,
+    }
+}
+
+/// A code block's label in a code group: its title, else its file, else its
+/// language. Its options are reported when the block itself renders.
+pub fn code_label(parameters: &[String]) -> String {
+    let (lang, _, rest) = split_parameters(parameters);
+    let options = crate::diagnostics::discard(|| CodeOptions::parse(rest));
+    options
+        .title
+        .or(options.file)
+        .unwrap_or_else(|| lang.to_string())
+}
+
+/// The language to highlight, whether the block is a diff of it, and the
+/// options after it.
+fn split_parameters(parameters: &[String]) -> (&str, bool, &[String]) {
+    let lang = parameters
+        .first()
+        .filter(|s| !s.is_empty())
+        .map_or("text", String::as_str);
+    match parameters.get(1) {
+        Some(inner) if lang == "diff" && is_language(inner) => (inner, true, &parameters[2..]),
+        _ => (lang, false, parameters.get(1..).unwrap_or_default()),
+    }
+}
+
+/// Whether a parameter after `diff` names the language of the diffed code,
+/// rather than being an option.
+fn is_language(parameter: &str) -> bool {
+    !(parameter.is_empty()
+        || parameter.contains('=')
+        || parameter.starts_with('{')
+        || parameter == "linenos")
+}
+
+/// The diff's code with each line's `+`, `-` or space marker removed, and
+/// what each line does. Lines without a marker (hunk headers, blank lines)
+/// are kept whole as context.
+fn strip_diff(diff: &str) -> (String, Vec<LineChange>) {
+    let (lines, changes): (Vec<&str>, Vec<LineChange>) = diff
+        .split('\n')
+        .map(|line| match line.as_bytes().first() {
+            Some(b'+') => (&line[1..], LineChange::Added),
+            Some(b'-') => (&line[1..], LineChange::Removed),
+            Some(b' ') => (&line[1..], LineChange::Context),
+            _ => (line, LineChange::Context),
+        })
+        .unzip();
+    (lines.join("\n"), changes)
+}
+
+/// The ranges in a `{2,5-7}` set; malformed entries are reported and skipped.
+fn line_ranges(set: &str) -> Vec<RangeInclusive<usize>> {
+    let inner = set.trim_start_matches('{').trim_end_matches('}');
+    inner
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let (first, last) = entry.split_once('-').unwrap_or((entry, entry));
+            match (first.trim().parse(), last.trim().parse()) {
+                (Ok(first), Ok(last)) if first >= 1 && first <= last => Some(first..=last),
+                _ => {
+                    crate::diagnostics::warn(format!(
+                        "invalid highlighted line range '{entry}' — ignored"
+                    ));
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// A `lines=` range: `10-40`, or `12` for a single line.
+fn line_range(value: &str) -> Option<RangeInclusive<usize>> {
+    let (first, last) = value.split_once('-').unwrap_or((value, value));
+    match (first.parse(), last.parse()) {
+        (Ok(first), Ok(last)) if first >= 1 && first <= last => Some(first..=last),
+        _ => None,
+    }
+}
+
+/// The lines between `#region name` and its `#endregion` markers, written in
+/// whatever comment syntax the file uses. Markers of regions nested inside are
+/// dropped.
+fn region(text: &str, name: &str, file: &str) -> Option<String> {
+    let mut lines = text.lines();
+    if !lines.by_ref().any(|line| region_start(line) == Some(name)) {
+        crate::diagnostics::warn(format!("region '{name}' not found in {file}"));
+        return None;
+    }
+    let mut depth = 0;
+    let mut body = Vec::new();
+    for line in lines {
+        if region_start(line).is_some() {
+            depth += 1;
+        } else if line.contains("#endregion") {
+            if depth == 0 {
+                return Some(body.join("\n"));
+            }
+            depth -= 1;
+        } else {
+            body.push(line);
+        }
+    }
+    crate::diagnostics::warn(format!(
+        "region '{name}' in {file} has no #endregion — included to the end of the file"
+    ));
+    Some(body.join("\n"))
+}
+
+/// The name after a `#region` marker on `line`.
+fn region_start(line: &str) -> Option<&str> {
+    let (_, rest) = line.split_once("#region")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    rest.split_whitespace().next()
+}
+
+/// The lines of `text` in `range`, cut short (and reported) at its end.
+fn select_lines(text: &str, range: &RangeInclusive<usize>, file: &str) -> Option<String> {
+    let count = text.lines().count();
+    if *range.end() > count {
+        crate::diagnostics::warn(format!(
+            "lines {}-{} are past the end of {file} ({count} lines)",
+            range.start(),
+            range.end()
+        ));
+    }
+    if *range.start() > count {
+        return None;
+    }
+    let lines: Vec<&str> = text
+        .lines()
+        .skip(range.start() - 1)
+        .take(range.end() - range.start() + 1)
+        .collect();
+    Some(lines.join("\n"))
+}
+
+fn highlight_lines(code: &str, mut spans: Vec<Span>, options: &CodeOptions) -> String {
+    spans.sort_by_key(|s| s.start);
+    let mut cursor = 0usize;
+    wrap_lines(code, options, |line, line_start, line_end, out| {
+        while cursor < spans.len() && spans[cursor].end <= line_start {
+            cursor += 1;
+        }
+
+        let clipped = spans[cursor..]
+            .iter()
+            .take_while(|span| span.start < line_end)
+            .filter(|span| span.end > line_start)
+            .map(|span| Span {
+                start: span.start.max(line_start) - line_start,
+                end: span.end.min(line_end) - line_start,
+                capture: span.capture.clone(),
+                pattern_index: span.pattern_index,
+            })
+            .collect();
+        out.push_str(&spans_to_html(line, clipped, &HtmlFormat::CustomElements));
+    })
+}
+
+fn wrap_plain_lines(text: &str, options: &CodeOptions) -> String {
+    wrap_lines(text, options, |line, _, _, out| out.push_str(line))
+}
+
+fn wrap_lines(
+    source: &str,
+    options: &CodeOptions,
+    mut body: impl FnMut(&str, u32, u32, &mut String),
+) -> String {
+    if source.is_empty() {
+        return String::new();
+    }
+    let mut out = String::with_capacity(source.len() + source.len() / 4);
+    let mut line_start = 0u32;
+    for (i, line) in source.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        out.push_str(&options.line_open(i));
+        let line_end = line_start + line.len() as u32;
+        body(line, line_start, line_end, &mut out);
+        out.push_str("</span>");
+        line_start = line_end + 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(parameters: &[&str]) -> Vec<String> {
+        parameters.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn cuts_included_text_to_regions_and_lines() {
+        let text = "use std::io;\n// #region setup\nlet a = 1;\n    // #region inner\n    let b = 2;\n    // #endregion\n// #endregion\nrun();";
+        let (setup, diagnostics) = crate::diagnostics::capture(|| region(text, "setup", "main.rs"));
+        assert_eq!(setup.as_deref(), Some("let a = 1;\n    let b = 2;"));
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        let (missing, diagnostics) =
+            crate::diagnostics::capture(|| region(text, "teardown", "main.rs"));
+        assert_eq!(missing, None);
+        assert_eq!(diagnostics, ["region 'teardown' not found in main.rs"]);
+
+        assert_eq!(line_range("2-3"), Some(2..=3));
+        assert_eq!(line_range("4"), Some(4..=4));
+        assert_eq!(line_range("3-2"), None);
+        let (lines, diagnostics) =
+            crate::diagnostics::capture(|| select_lines(text, &(7..=9), "main.rs"));
+        assert_eq!(lines.as_deref(), Some("// #endregion\nrun();"));
+        assert_eq!(
+            diagnostics,
+            ["lines 7-9 are past the end of main.rs (8 lines)"]
+        );
+    }
+
+    #[test]
+    fn parses_line_numbers_and_highlight_sets() {
+        let (options, diagnostics) = crate::diagnostics::capture(|| {
+            CodeOptions::parse(&strings(&[
+                "{2,",
+                "5-7,",
+                "9-8}",
+                "linenos=10",
+                r#"title="Setup"#,
+                r#"<script>""#,
+                "wrap",
+            ]))
+        });
+        assert_eq!(
+            options.caption().as_deref(),
+            Some(r#"<figcaption class="code-title">Setup &lt;script&gt;</figcaption>"#)
+        );
+        assert_eq!(options.line_numbers, Some(10));
+        assert_eq!(options.highlighted, [2..=2, 5..=7]);
+        assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+
+        assert_eq!(
+            options.line_open(1),
+            r#"<span class="line highlighted" data-line="11">"#
+        );
+        assert_eq!(
+            options.line_open(2),
+            r#"<span class="line" data-line="12">"#
+        );
+        assert_eq!(
+            CodeOptions::default().line_open(0),
+            r#"<span class="line">"#
+        );
+    }
+
+    #[test]
+    fn strip_diff_removes_markers() {
+        let (code, changes) = strip_diff("@@ -1 +1 @@\n-let a = 1;\n+let a = 2;\n ok();");
+        assert_eq!(code, "@@ -1 +1 @@\nlet a = 1;\nlet a = 2;\nok();");
+        assert_eq!(
+            changes,
+            [
+                LineChange::Context,
+                LineChange::Removed,
+                LineChange::Added,
+                LineChange::Context
+            ]
+        );
+    }
+
+    #[test]
+    fn highlight_lines_clips_multiline_spans() {
+        let span = Span {
+            start: 0,
+            end: 5,
+            capture: "comment".into(),
+            pattern_index: 0,
+        };
+        let out = highlight_lines("ab\ncd", vec![span], &CodeOptions::default());
+        assert_eq!(out.matches(r#"<span class="line">"#).count(), 2, "{out}");
+        assert!(out.contains("ab") && out.contains("cd"), "{out}");
+    }
+
+    #[test]
+    fn wrap_plain_lines_wraps_each_line() {
+        assert_eq!(
+            wrap_plain_lines("a\nb", &CodeOptions::default()),
+            "<span class=\"line\">a</span>\n<span class=\"line\">b</span>"
+        );
+    }
+}
@@ -1,4 +1,5 @@
-use crate::segments::{DocumentIds, convert_segments_with_ids, title_key, title_slug};
+use super::{BlockAttrs, carryover_id};
+use crate::segments::{DocumentIds, HeadingSlot, convert_segments_with_ids, title_key, title_slug};
 use rust_norg::{
     CarryoverTag as CarryoverKind, DelimitingModifier, NorgAST, NorgASTFlat, ParagraphSegment,
     RangeableDetachedModifier,
@@ -21,6 +22,12 @@ pub fn is_comment_tag(name: &[String]) -> bool {
     matches!(name, [name] if name == "comment")
 }
 
+/// `#tangle path` and `#tangle.none`, which only [`crate::tangle`] reads; the
+/// renderer passes them over silently.
+pub fn is_tangle_tag(name: &[String]) -> bool {
+    name.first().is_some_and(|tag| tag == "tangle")
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum CommentKind {
     Weak,
@@ -51,7 +58,7 @@ pub fn comment_target(mut node: &NorgAST) -> Option<(CommentKind, &NorgAST)> {
     comment.map(|kind| (kind, node))
 }
 
-fn carryover_target(mut node: &NorgAST) -> &NorgAST {
+pub fn carryover_target(mut node: &NorgAST) -> &NorgAST {
     while let NorgAST::CarryoverTag { next_object, .. } = node {
         node = next_object;
     }
@@ -115,21 +122,110 @@ pub fn flat_heading_level(node: &NorgASTFlat) -> Option<u16> {
     }
 }
 
+/// The index just past what the comment on `content[index]` hides, or `None`
+/// if it isn't commented — the flat counterpart of [`comment_scope`].
+pub fn flat_comment_end(content: &[NorgASTFlat], index: usize) -> Option<usize> {
+    let (kind, target) = flat_comment_target(&content[index])?;
+
+    if let Some(level) = flat_heading_level(target) {
+        let mut next = index + 1;
+        let mut current_level = level as i16;
+        while next < content.len() {
+            match flat_heading_level(&content[next]) {
+                Some(next_level) if next_level <= level || kind == CommentKind::Weak => break,
+                Some(next_level) => current_level = next_level as i16,
+                None if matches!(
+                    &content[next],
+                    NorgASTFlat::DelimitingModifier(delim)
+                        if delimiter_exits_heading_scope(
+                            delim,
+                            level,
+                            &mut current_level,
+                        )
+                ) =>
+                {
+                    break;
+                }
+                None => {}
+            }
+            next += 1;
+        }
+        return Some(next);
+    }
+
+    if kind == CommentKind::Strong
+        && let NorgASTFlat::NestableDetachedModifier {
+            modifier_type,
+            level,
+            ..
+        } = target
+    {
+        let mut next = index + 1;
+        while next < content.len() {
+            let NorgASTFlat::NestableDetachedModifier {
+                modifier_type: next_type,
+                level: next_level,
+                ..
+            } = flat_carryover_target(&content[next])
+            else {
+                break;
+            };
+            if next_level < level || (next_level == level && next_type != modifier_type) {
+                break;
+            }
+            next += 1;
+        }
+        return Some(next);
+    }
+
+    Some(index + 1)
+}
+
 pub fn document_ids(ast: &[NorgAST]) -> DocumentIds {
     let mut headings = Vec::new();
     let mut footnotes = Vec::new();
+    let mut custom_ids = Vec::new();
+    // Carryovers are visited before the node they annotate, so a heading's
+    // `+id` waits here until the heading claims it.
+    let mut pending_id = None;
     visit_visible_nodes(ast, &mut |node| match node {
-        NorgAST::Heading { level, title, .. } => {
-            headings.push((*level, title_key(title), title_slug(title)));
+        NorgAST::CarryoverTag {
+            name, parameters, ..
+        } => {
+            if let Some(id) = carryover_id(name, parameters) {
+                custom_ids.push(id.clone());
+                pending_id = Some(id);
+            }
         }
+        NorgAST::Heading { level, title, .. } => headings.push(HeadingSlot {
+            level: *level,
+            key: title_key(title),
+            slug: title_slug(title),
+            custom_id: pending_id.take(),
+        }),
         NorgAST::RangeableDetachedModifier {
             modifier_type: RangeableDetachedModifier::Footnote,
             title,
             ..
-        } => footnotes.push((title_key(title), title_slug(title))),
-        _ => {}
+        } => {
+            pending_id = None;
+            footnotes.push((title_key(title), title_slug(title)));
+        }
+        _ => pending_id = None,
     });
-    DocumentIds::new(headings, footnotes)
+    DocumentIds::new(headings, footnotes, &custom_ids)
+}
+
+/// Whether `node` is a list annotated by carryovers other than comments. The
+/// renderer starts a new list run there, so the annotation covers exactly the
+/// list it precedes.
+pub fn is_annotated_list(node: &NorgAST) -> bool {
+    matches!(node, NorgAST::CarryoverTag { .. })
+        && comment_target(node).is_none()
+        && matches!(
+            carryover_target(node),
+            NorgAST::List { .. } | NorgAST::NestableDetachedModifier { .. }
+        )
 }
 
 pub fn visit_visible_headings<'a>(
@@ -228,6 +324,7 @@ fn visit_visible_nodes<'a>(nodes: &'a [NorgAST], visit: &mut impl FnMut(&'a Norg
                 visit_visible_nodes(content, visit);
             }
             NorgAST::CarryoverTag { next_object, .. } => {
+                visit(&nodes[index]);
                 visit_visible_nodes(std::slice::from_ref(next_object), visit);
             }
             _ => visit(&nodes[index]),
@@ -236,8 +333,26 @@ fn visit_visible_nodes<'a>(nodes: &'a [NorgAST], visit: &mut impl FnMut(&'a Norg
     }
 }
 
+/// A tag parameter's value with the quotes stripped. A quoted value that
+/// spans parameters (`title="A view"` arrives as `title="A` and `view"`) takes
+/// the ones after it from `rest`, up to the closing quote.
+pub fn quoted_value(value: &str, rest: &mut std::slice::Iter<'_, String>) -> String {
+    let mut value = value.to_string();
+    if value.starts_with('"') {
+        while !(value.len() > 1 && value.ends_with('"')) {
+            let Some(next) = rest.next() else {
+                break;
+            };
+            value.push(' ');
+            value.push_str(next);
+        }
+        value = value.trim_matches('"').to_string();
+    }
+    value
+}
+
 /// Records a skipped tag the renderer doesn't implement, naming its kind and
-/// the dotted tag name (e.g. `image.gallery`) so the dropped content is traceable.
+/// the dotted tag name (e.g. `table.cells`) so the dropped content is traceable.
 pub fn warn_unimplemented(kind: &str, name: &[String]) {
     crate::diagnostics::warn(format!(
         "unimplemented {kind} tag '{}' — content skipped",
@@ -254,6 +369,16 @@ pub fn warn_carryover_ignored(name: &[String]) {
     ));
 }
 
+/// Records `+id`/`+class`/`+attr` carryovers on a node that can't take them.
+pub fn warn_attributes_ignored(attrs: &BlockAttrs) {
+    if !attrs.is_empty() {
+        crate::diagnostics::warn(
+            "carryover attributes apply only to headings, paragraphs, lists, code blocks \
+             and images — ignored",
+        );
+    }
+}
+
 pub fn delimiter_exits_heading_scope(
     delim: &DelimitingModifier,
     start_level: u16,
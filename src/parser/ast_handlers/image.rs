@@ -0,0 +1,269 @@
+use super::quoted_value;
+use crate::image_size::image_size;
+use crate::includes::{include_root, is_within};
+use crate::segments::{DocumentIds, convert_segments_with_ids};
+use crate::types::AssetKind;
+use crate::utils::{UrlKind, document_relative, resource_src};
+use htmlescape::encode_minimal;
+use rust_norg::NorgAST;
+use std::fmt::Write;
+use std::io::ErrorKind;
+
+/// Presentation options for an image, from `key=value` parameters after its
+/// source or from carryovers (`+width 800`, `+caption …`) on the tag.
+#[derive(Clone, Default)]
+pub struct ImageOptions {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    loading: Option<&'static str>,
+    decoding: Option<&'static str>,
+    title: Option<String>,
+    /// Norg markup; wraps the image in a `<figure>`.
+    caption: Option<String>,
+}
+
+/// Option names an image takes, as parameters or carryovers.
+pub const IMAGE_OPTIONS: &[&str] = &["width", "height", "loading", "decoding", "title", "caption"];
+
+impl ImageOptions {
+    /// Reads `key=value` parameters, reporting unknown keys and invalid
+    /// values. A quoted value may span parameters (`title="A view"`).
+    pub fn parse(parameters: &[String]) -> Self {
+        let mut options = Self::default();
+        for (key, value) in key_values(parameters) {
+            let valid = match key.as_str() {
+                "width" => value.parse().map(|v| options.width = Some(v)).is_ok(),
+                "height" => value.parse().map(|v| options.height = Some(v)).is_ok(),
+                "loading" => {
+                    options.loading = ["lazy", "eager"]
+                        .into_iter()
+                        .find(|allowed| *allowed == value);
+                    options.loading.is_some()
+                }
+                "decoding" => {
+                    options.decoding = ["async", "sync", "auto"]
+                        .into_iter()
+                        .find(|allowed| *allowed == value);
+                    options.decoding.is_some()
+                }
+                "title" => {
+                    options.title = Some(value.clone());
+                    true
+                }
+                "caption" => {
+                    options.caption = Some(value.clone());
+                    true
+                }
+                _ => {
+                    crate::diagnostics::warn(format!("unknown image option '{key}' — ignored"));
+                    continue;
+                }
+            };
+            if !valid {
+                crate::diagnostics::warn(format!(
+                    "invalid value '{value}' for image option '{key}' — ignored"
+                ));
+            }
+        }
+        options
+    }
+}
+
+fn key_values(parameters: &[String]) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut parameters = parameters.iter();
+    while let Some(parameter) = parameters.next() {
+        let Some((key, value)) = parameter.split_once('=') else {
+            crate::diagnostics::warn(format!(
+                "image option '{parameter}' is not key=value — ignored"
+            ));
+            continue;
+        };
+        let value = quoted_value(value, &mut parameters);
+        pairs.push((key.to_ascii_lowercase(), value));
+    }
+    pairs
+}
+
+/// An `<img>` for `path`, in a `<figure>` when captioned. `None` if the source
+/// is refused by the URL policy: an image is fetched on load, so a refused
+/// scheme drops the whole tag, like a link's.
+pub fn render_image(
+    path: &str,
+    alt: &str,
+    options: &ImageOptions,
+    ids: &DocumentIds,
+) -> Option<String> {
+    let src = resource_src(path, AssetKind::image)?;
+
+    let mut img = format!(
+        r#"<img src="{}" alt="{}""#,
+        encode_minimal(&src),
+        encode_minimal(alt)
+    );
+    let (width, height) = dimensions(path, options);
+    if let Some(width) = width {
+        let _ = write!(img, r#" width="{width}""#);
+    }
+    if let Some(height) = height {
+        let _ = write!(img, r#" height="{height}""#);
+    }
+    if let Some(loading) = options.loading {
+        let _ = write!(img, r#" loading="{loading}""#);
+    }
+    if let Some(decoding) = options.decoding {
+        let _ = write!(img, r#" decoding="{decoding}""#);
+    }
+    if let Some(title) = &options.title {
+        let _ = write!(img, r#" title="{}""#, encode_minimal(title));
+    }
+    img.push_str(" />");
+
+    Some(match &options.caption {
+        Some(caption) => format!(
+            "<figure>{img}<figcaption>{}</figcaption></figure>",
+            caption_html(caption, ids)
+        ),
+        None => img,
+    })
+}
+
+/// A `<div class="gallery">` of figures, one per `path alt text…` line of
+/// `content`. `options` apply to every image; a caption has no single image
+/// to label, so it is reported and dropped.
+pub fn render_gallery(content: &str, options: &ImageOptions, ids: &DocumentIds) -> String {
+    let mut options = options.clone();
+    if options.caption.take().is_some() {
+        crate::diagnostics::warn("image.gallery takes no caption — ignored");
+    }
+    let mut html = String::from(r#"<div class="gallery">"#);
+    for line in content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        let (path, alt) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(path, alt)| (path, alt.trim()));
+        if let Some(img) = render_image(path, alt, &options, ids) {
+            let _ = write!(html, "<figure>{img}</figure>");
+        }
+    }
+    html.push_str("</div>");
+    html
+}
+
+/// The given `width`/`height`, completed from the file's intrinsic size: both
+/// when neither is set, or the missing one scaled to keep the aspect ratio.
+fn dimensions(path: &str, options: &ImageOptions) -> (Option<u32>, Option<u32>) {
+    if let (Some(width), Some(height)) = (options.width, options.height) {
+        return (Some(width), Some(height));
+    }
+    let Some((intrinsic_width, intrinsic_height)) = local_image_size(path) else {
+        return (options.width, options.height);
+    };
+    let scale = |value: u32, to: u32, from: u32| {
+        ((u64::from(value) * u64::from(to) + u64::from(from) / 2) / u64::from(from)) as u32
+    };
+    match (options.width, options.height) {
+        (Some(width), _) => (
+            Some(width),
+            Some(scale(intrinsic_height, width, intrinsic_width)),
+        ),
+        (_, Some(height)) => (
+            Some(scale(intrinsic_width, height, intrinsic_height)),
+            Some(height),
+        ),
+        _ => (Some(intrinsic_width), Some(intrinsic_height)),
+    }
+}
+
+/// Reads the size of a bare relative image source, resolved against the
+/// document's directory. Only with `file_path` set; rooted and remote sources
+/// aren't on a path this parser knows. Like includes, images outside the
+/// include root aren't read, nor anything but regular files.
+fn local_image_size(path: &str) -> Option<(u32, u32)> {
+    if !UrlKind::of(path).is_site_relative() || path.starts_with('/') {
+        return None;
+    }
+    let relative = path.split(['?', '#']).next().unwrap_or_default();
+    let resolved = document_relative(relative)?;
+    let root = include_root();
+    if !is_within(&resolved, &root) {
+        crate::diagnostics::warn(format!(
+            "not reading the size of image {path}: it is outside {} (set includeRoot to allow it)",
+            root.display()
+        ));
+        return None;
+    }
+    if std::fs::metadata(&resolved).is_ok_and(|metadata| !metadata.is_file()) {
+        crate::diagnostics::warn(format!("not reading the size of image {path}: not a file"));
+        return None;
+    }
+    match image_size(&resolved) {
+        Ok(Some(size)) => Some(size),
+        Ok(None) => {
+            crate::diagnostics::warn(format!(
+                "could not read the size of image {path} — not a PNG, JPEG, GIF, WebP or \
+                 pixel-sized SVG"
+            ));
+            None
+        }
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            crate::diagnostics::warn(format!(
+                "image not found: {path} (looked for {})",
+                resolved.display()
+            ));
+            None
+        }
+        Err(error) => {
+            crate::diagnostics::warn(format!("could not read image {path}: {error}"));
+            None
+        }
+    }
+}
+
+/// Renders caption text as inline Norg, so it can carry links and markup.
+fn caption_html(caption: &str, ids: &DocumentIds) -> String {
+    let Ok(ast) = rust_norg::parse_tree(caption) else {
+        return encode_minimal(caption);
+    };
+    let paragraphs: Vec<String> = ast
+        .iter()
+        .filter_map(|node| match node {
+            NorgAST::Paragraph(segments) => Some(convert_segments_with_ids(segments, ids)),
+            _ => None,
+        })
+        .collect();
+    if paragraphs.is_empty() {
+        return encode_minimal(caption);
+    }
+    paragraphs.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(parameters: &[&str]) -> Vec<String> {
+        parameters.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_quoted_and_validated_options() {
+        let (options, diagnostics) = crate::diagnostics::capture(|| {
+            ImageOptions::parse(&strings(&[
+                "width=800",
+                r#"title="A"#,
+                "wide",
+                r#"view""#,
+                "loading=sometimes",
+                "colour=red",
+            ]))
+        });
+        assert_eq!(options.width, Some(800));
+        assert_eq!(options.title.as_deref(), Some("A wide view"));
+        assert_eq!(options.loading, None);
+        assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+    }
+}
@@ -0,0 +1,20 @@
+/// Standard infirm tags (`.name parameters`), which have no body.
+pub enum InfirmTag {
+    /// `.image path options…`, the single-line form of `@image`.
+    Image,
+    /// `.video` and `.audio`, likewise.
+    Video,
+    Audio,
+    Unknown,
+}
+
+impl From<&[String]> for InfirmTag {
+    fn from(name: &[String]) -> Self {
+        match name {
+            [tag] if tag == "image" => Self::Image,
+            [tag] if tag == "video" => Self::Video,
+            [tag] if tag == "audio" => Self::Audio,
+            _ => Self::Unknown,
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use super::quoted_value;
+use crate::types::AssetKind;
+use crate::utils::resource_src;
+use htmlescape::encode_minimal;
+use std::fmt::Write;
+
+/// The element a media tag renders as.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Video,
+    Audio,
+}
+
+impl MediaKind {
+    fn tag(self) -> &'static str {
+        match self {
+            Self::Video => "video",
+            Self::Audio => "audio",
+        }
+    }
+
+    fn asset(self) -> AssetKind {
+        match self {
+            Self::Video => AssetKind::video,
+            Self::Audio => AssetKind::audio,
+        }
+    }
+}
+
+/// Options after a media tag's source: `poster=`, `captions=` (repeatable)
+/// and `lang=` as `key=value`, and the bare flags `autoplay`, `muted` and
+/// `loop`.
+#[derive(Default)]
+pub struct MediaOptions {
+    poster: Option<String>,
+    captions: Vec<String>,
+    lang: Option<String>,
+    autoplay: bool,
+    muted: bool,
+    looped: bool,
+}
+
+impl MediaOptions {
+    /// Reads the options, reporting unknown ones and a poster on audio.
+    /// Values may be quoted to hold spaces.
+    pub fn parse(kind: MediaKind, parameters: &[String]) -> Self {
+        let mut options = Self::default();
+        let mut parameters = parameters.iter();
+        while let Some(parameter) = parameters.next() {
+            match parameter.split_once('=') {
+                Some(("poster", value)) if kind == MediaKind::Video => {
+                    options.poster = Some(quoted_value(value, &mut parameters));
+                }
+                Some(("captions", value)) => {
+                    options.captions.push(quoted_value(value, &mut parameters));
+                }
+                Some(("lang", value)) => options.lang = Some(quoted_value(value, &mut parameters)),
+                None if parameter == "autoplay" => options.autoplay = true,
+                None if parameter == "muted" => options.muted = true,
+                None if parameter == "loop" => options.looped = true,
+                Some((key, value)) => crate::diagnostics::warn(format!(
+                    "unknown {} option '{key}={}' — ignored",
+                    kind.tag(),
+                    quoted_value(value, &mut parameters)
+                )),
+                None => crate::diagnostics::warn(format!(
+                    "unknown {} option '{parameter}' — ignored",
+                    kind.tag()
+                )),
+            }
+        }
+        options
+    }
+}
+
+/// A `<video controls>` or `<audio controls>` for `path`, with a `<track>`
+/// per caption file and `fallback` text for browsers that can't play it.
+/// `None` if the source is refused by the URL policy; a refused poster or
+/// track only drops itself.
+pub fn render_media(
+    kind: MediaKind,
+    path: &str,
+    fallback: &str,
+    options: &MediaOptions,
+) -> Option<String> {
+    let src = resource_src(path, kind.asset())?;
+    let tag = kind.tag();
+    let mut html = format!(r#"<{tag} controls src="{}""#, encode_minimal(&src));
+    if let Some(poster) = options
+        .poster
+        .as_deref()
+        .and_then(|poster| resource_src(poster, AssetKind::image))
+    {
+        let _ = write!(html, r#" poster="{}""#, encode_minimal(&poster));
+    }
+    for (flag, set) in [
+        ("autoplay", options.autoplay),
+        ("muted", options.muted),
+        ("loop", options.looped),
+    ] {
+        if set {
+            let _ = write!(html, " {flag}");
+        }
+    }
+    html.push('>');
+
+    for track in &options.captions {
+        let Some(src) = resource_src(track, AssetKind::track) else {
+            continue;
+        };
+        let _ = write!(
+            html,
+            r#"<track kind="captions" src="{}""#,
+            encode_minimal(&src)
+        );
+        if let Some(lang) = &options.lang {
+            let _ = write!(html, r#" srclang="{}""#, encode_minimal(lang));
+        }
+        html.push_str(" />");
+    }
+    html.push_str(&encode_minimal(fallback));
+    let _ = write!(html, "</{tag}>");
+    Some(html)
+}
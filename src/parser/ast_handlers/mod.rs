@@ -1,9 +1,21 @@
+mod carryover;
+mod code;
 mod error;
 mod helpers;
+mod image;
+mod infirm;
+mod media;
 mod nestable;
+mod ranged;
 mod verbatim;
 
+pub use carryover::*;
+pub use code::*;
 pub use error::*;
 pub use helpers::*;
+pub use image::*;
+pub use infirm::*;
+pub use media::*;
 pub use nestable::*;
+pub use ranged::*;
 pub use verbatim::*;
@@ -96,6 +96,33 @@ pub fn collect_list_items<'a>(node: &'a NorgAST, events: &mut Vec<FlatListEvent<
     }
 }
 
+/// Flattens one list item from a ranged tag's flat content, where rust-norg
+/// gives items no nested children. Returns whether `node` was a list item.
+pub fn collect_flat_list_item<'a>(
+    node: &'a NorgASTFlat,
+    events: &mut Vec<FlatListEvent<'a>>,
+) -> bool {
+    let NorgASTFlat::NestableDetachedModifier {
+        modifier_type,
+        level,
+        extensions,
+        content,
+    } = node
+    else {
+        return false;
+    };
+    match content.as_ref() {
+        NorgASTFlat::Paragraph(text) => events.push(FlatListEvent::Item(FlatListItem {
+            kind: *modifier_type,
+            level: *level,
+            text,
+            extensions,
+        })),
+        _ => crate::diagnostics::warn("list item text is not a paragraph — item text skipped"),
+    }
+    true
+}
+
 fn collect_list_children<'a>(nodes: &'a [NorgAST], events: &mut Vec<FlatListEvent<'a>>) {
     for node in nodes {
         // rust-norg keeps a list item's indented blocks as siblings, so its
@@ -0,0 +1,298 @@
+use super::{carryover_target, comment_scope, flat_comment_end, is_comment_tag};
+use rust_norg::{NorgAST, NorgASTFlat};
+use textwrap::dedent;
+
+/// Standard ranged tags (`|name … |end`). Comments are handled before dispatch.
+pub enum RangedTag {
+    Details,
+    Example,
+    Group,
+    /// `@code` blocks shown as tabs, labelled by each block's title or
+    /// language.
+    CodeGroup,
+    /// A configured callout kind (`|note`, `|warning`, …).
+    Callout(String),
+    Unknown,
+}
+
+/// Callout kinds recognised when the `callouts` option is unset.
+const DEFAULT_CALLOUTS: &[&str] = &["note", "tip", "warning", "danger"];
+
+impl From<&[String]> for RangedTag {
+    fn from(name: &[String]) -> Self {
+        match name {
+            [tag] if tag == "details" => Self::Details,
+            [tag] if tag == "example" => Self::Example,
+            [tag] if tag == "group" => Self::Group,
+            [tag] if tag == "code-group" => Self::CodeGroup,
+            [tag] if is_callout(tag) => Self::Callout(tag.clone()),
+            _ => Self::Unknown,
+        }
+    }
+}
+
+fn is_callout(tag: &str) -> bool {
+    crate::options::with(|options| match &options.callouts {
+        Some(kinds) => kinds.iter().any(|kind| kind == tag),
+        None => DEFAULT_CALLOUTS.contains(&tag),
+    })
+}
+
+/// The raw Norg source of each `|example`, which the AST no longer has.
+/// Examples are found twice — scanning the source, and walking every node
+/// (hidden ones included) — and paired by their ordinal in document order.
+/// The walk also notes which examples the renderer reaches; their sources are
+/// handed out in that order, so it must agree with the renderer on what is
+/// shown, as the id pre-pass does.
+#[derive(Default)]
+pub struct ExampleSources {
+    /// Sources of the rendered examples, in document order; empty when the
+    /// examples couldn't be paired with the source.
+    sources: Vec<String>,
+    taken: usize,
+}
+
+impl ExampleSources {
+    pub fn new(ast: &[NorgAST], source: &str) -> Self {
+        let mut rendered = Vec::new();
+        collect_examples(ast, true, &mut rendered);
+        let sources = scan_examples(source);
+        if rendered.is_empty() {
+            return Self::default();
+        }
+        if rendered.len() != sources.len() {
+            crate::diagnostics::warn(format!(
+                "found {} |example tags in the source but {} in the parsed document — \
+                 examples render without their source",
+                sources.len(),
+                rendered.len()
+            ));
+            return Self::default();
+        }
+        Self {
+            sources: rendered
+                .into_iter()
+                .zip(sources)
+                .filter_map(|(rendered, source)| rendered.then_some(source))
+                .collect(),
+            taken: 0,
+        }
+    }
+
+    /// The source of the next example the renderer reaches.
+    pub fn next_source(&mut self) -> Option<&str> {
+        self.taken += 1;
+        self.sources.get(self.taken - 1).map(String::as_str)
+    }
+
+    /// Whether every source was taken, and no more than that; always true
+    /// when the examples couldn't be paired.
+    pub fn all_taken(&self) -> bool {
+        self.sources.is_empty() || self.taken == self.sources.len()
+    }
+}
+
+/// Records, for each `|example` under `nodes` in document order, whether the
+/// renderer reaches it — it doesn't for hidden nodes, nor inside lists and
+/// definition/footnote/table bodies, which render without the tag.
+fn collect_examples(nodes: &[NorgAST], rendered: bool, out: &mut Vec<bool>) {
+    let mut index = 0;
+    while index < nodes.len() {
+        if let Some(scope) = comment_scope(nodes, index) {
+            for node in &nodes[index..scope.end] {
+                collect_commented_example(node, &scope.visible, rendered, out);
+            }
+            index = scope.end;
+            continue;
+        }
+        collect_node_examples(&nodes[index], rendered, out);
+        index += 1;
+    }
+}
+
+/// A node within a comment's range: only the headings it leaves visible, and
+/// what they hold, are rendered.
+fn collect_commented_example(
+    node: &NorgAST,
+    visible: &[&NorgAST],
+    rendered: bool,
+    out: &mut Vec<bool>,
+) {
+    if visible.iter().any(|shown| std::ptr::eq(*shown, node)) {
+        collect_examples(std::slice::from_ref(node), rendered, out);
+    } else if let NorgAST::Heading { content, .. } = carryover_target(node) {
+        for node in content {
+            collect_commented_example(node, visible, rendered, out);
+        }
+    } else {
+        collect_node_examples(node, false, out);
+    }
+}
+
+fn collect_node_examples(node: &NorgAST, rendered: bool, out: &mut Vec<bool>) {
+    match node {
+        NorgAST::Heading { content, .. } => collect_examples(content, rendered, out),
+        NorgAST::NestableDetachedModifier { content, .. } => {
+            content
+                .iter()
+                .for_each(|node| collect_node_examples(node, false, out));
+        }
+        NorgAST::List { items, .. } => items
+            .iter()
+            .for_each(|node| collect_node_examples(node, false, out)),
+        NorgAST::CarryoverTag { next_object, .. } => {
+            collect_node_examples(next_object, rendered, out);
+        }
+        NorgAST::RangeableDetachedModifier { content, .. } => {
+            collect_flat_examples(content, false, out);
+        }
+        NorgAST::RangedTag { name, content, .. } => {
+            collect_tag_examples(name, content, rendered, out);
+        }
+        _ => {}
+    }
+}
+
+fn collect_flat_examples(nodes: &[NorgASTFlat], rendered: bool, out: &mut Vec<bool>) {
+    let mut index = 0;
+    while index < nodes.len() {
+        if let Some(next) = flat_comment_end(nodes, index) {
+            for node in &nodes[index..next] {
+                collect_flat_node_examples(node, false, out);
+            }
+            index = next;
+            continue;
+        }
+        collect_flat_node_examples(&nodes[index], rendered, out);
+        index += 1;
+    }
+}
+
+fn collect_flat_node_examples(node: &NorgASTFlat, rendered: bool, out: &mut Vec<bool>) {
+    match node {
+        NorgASTFlat::CarryoverTag { next_object, .. } => {
+            collect_flat_node_examples(next_object, rendered, out);
+        }
+        NorgASTFlat::NestableDetachedModifier { content, .. } => {
+            collect_flat_node_examples(content, false, out);
+        }
+        NorgASTFlat::RangeableDetachedModifier { content, .. } => {
+            collect_flat_examples(content, false, out);
+        }
+        NorgASTFlat::RangedTag { name, content, .. } => {
+            collect_tag_examples(name, content, rendered, out);
+        }
+        _ => {}
+    }
+}
+
+/// A `|name … |end` tag: comments and unknown tags render nothing, and a
+/// code group only its `@code` blocks.
+fn collect_tag_examples(
+    name: &[String],
+    content: &[NorgASTFlat],
+    rendered: bool,
+    out: &mut Vec<bool>,
+) {
+    let tag = if is_comment_tag(name) {
+        RangedTag::Unknown
+    } else {
+        RangedTag::from(name)
+    };
+    if matches!(tag, RangedTag::Example) {
+        out.push(rendered);
+    }
+    let rendered = rendered && !matches!(tag, RangedTag::CodeGroup | RangedTag::Unknown);
+    collect_flat_examples(content, rendered, out);
+}
+
+/// The dedented body of every `|example … |end` in `source`, in order of
+/// their opening lines. Verbatim tags are skipped whole, so an `|end` inside
+/// `@code` doesn't close anything.
+fn scan_examples(source: &str) -> Vec<String> {
+    let lines: Vec<&str> = source.lines().collect();
+    // An opener with no `@end` after it is prose, as it is to the parser.
+    let last_end = lines.iter().rposition(|line| line.trim() == "@end");
+    let mut found = Vec::new();
+    // Open ranged tags as (is_example, first body line).
+    let mut open: Vec<(bool, usize)> = Vec::new();
+    let mut in_verbatim = false;
+
+    for (index, line) in lines.iter().enumerate() {
+        let line = line.trim();
+        if in_verbatim {
+            in_verbatim = line != "@end";
+            continue;
+        }
+        if is_verbatim_opener(line) && last_end.is_some_and(|end| end > index) {
+            in_verbatim = true;
+        } else if line == "|end" {
+            if let Some((true, start)) = open.pop() {
+                found.push((start, dedent(&lines[start..index].join("\n"))));
+            }
+        } else if let Some(tag) = line.strip_prefix('|')
+            && tag.starts_with(|c: char| c.is_alphabetic())
+        {
+            let name = tag.split_whitespace().next().unwrap_or_default();
+            open.push((name == "example", index + 1));
+        }
+    }
+
+    // Closed innermost-first; document order is by opening line.
+    found.sort_by_key(|(start, _)| *start);
+    found
+        .into_iter()
+        .map(|(_, body)| body.trim_end().to_string())
+        .collect()
+}
+
+/// Whether `line` opens a verbatim tag: `@name` alone or followed by
+/// whitespace, other than `@end`.
+fn is_verbatim_opener(line: &str) -> bool {
+    let Some(rest) = line.strip_prefix('@') else {
+        return false;
+    };
+    let name = rest.split(char::is_whitespace).next().unwrap_or_default();
+    !name.is_empty()
+        && name != "end"
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_finds_nested_examples_and_skips_verbatim() {
+        let source = "\
+|example
+  * Heading
+  |example
+  inner
+  |end
+|end
+@code norg
+|example
+|end
+@end
+";
+        assert_eq!(
+            scan_examples(source),
+            ["* Heading\n|example\ninner\n|end", "inner"]
+        );
+    }
+
+    #[test]
+    fn scan_reads_prose_starting_with_at_as_prose() {
+        let source = "\
+@someone said this works
+@someone's reply: it does
+|example
+* Heading
+|end
+";
+        assert_eq!(scan_examples(source), ["* Heading"]);
+    }
+}
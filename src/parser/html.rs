@@ -1,8 +1,16 @@
 use crate::ast_handlers::*;
-use crate::segments::{DocumentIds, convert_segments_with_ids, heading_html_and_id};
-use crate::types::{EmbedComponent, OutputMode};
+use crate::sanitize::sanitize;
+use crate::segments::{
+    DocumentIds, HeadingParts, convert_segments_with_ids, heading_parts, id_prefix, output_level,
+    warn_invalid_id_prefix,
+};
+use crate::types::{EmbedComponent, OutputMode, PermalinkPosition};
 use arborium::Highlighter;
-use rust_norg::{NorgAST, NorgASTFlat, ParagraphSegment, RangeableDetachedModifier};
+use htmlescape::encode_minimal;
+use rust_norg::{
+    DelimitingModifier, NorgAST, NorgASTFlat, ParagraphSegment, RangeableDetachedModifier,
+};
+use std::fmt::Write;
 
 struct TransformState {
     parts: Vec<String>,
@@ -17,10 +25,18 @@ struct TransformState {
     /// component.
     embed_decls: usize,
     ids: DocumentIds,
+    /// Wrappers (`<section>`, `<details>`) still open around heading scopes,
+    /// as the heading's source level and the closing tag, innermost last.
+    /// Every open heading scope has an entry, with an empty closing tag when
+    /// it has no wrapper, so a delimiter finds the scope it actually exits.
+    scopes: Vec<(u16, &'static str)>,
+    examples: ExampleSources,
+    /// `|code-group` tags rendered so far, numbering their tab ids.
+    code_groups: usize,
 }
 
 impl TransformState {
-    fn new(mode: Option<OutputMode>, ids: DocumentIds) -> Self {
+    fn new(mode: Option<OutputMode>, ids: DocumentIds, examples: ExampleSources) -> Self {
         Self {
             parts: Vec::new(),
             current_html: String::new(),
@@ -30,6 +46,9 @@ impl TransformState {
             highlighter: Highlighter::new(),
             embed_decls: 0,
             ids,
+            scopes: Vec::new(),
+            examples,
+            code_groups: 0,
         }
     }
 
@@ -39,9 +58,10 @@ impl TransformState {
     }
 
     /// Renders a flattened list run and appends it, skipping an empty render.
-    fn push_list(&mut self, events: &[FlatListEvent]) {
-        let html = render_list_items(events, &self.ids);
+    fn push_list(&mut self, events: &[FlatListEvent], attrs: &BlockAttrs) {
+        let mut html = render_list_items(events, &self.ids);
         if !html.is_empty() {
+            attrs.inject(&mut html);
             self.push_html(&html);
         }
     }
@@ -51,6 +71,18 @@ impl TransformState {
             VerbatimTagResult::Html(html) => self.push_html(&html),
             VerbatimTagResult::Css(css) => self.css_blocks.push(css),
             VerbatimTagResult::Embed { mode, code } => {
+                // Component modes render each part into its own container, so
+                // a section can't span the embed; html concatenates the parts.
+                if self.mode != Some(OutputMode::html) {
+                    if self.scopes.iter().any(|&(_, close)| !close.is_empty()) {
+                        crate::diagnostics::warn(format!(
+                            "embed #{} closes the sections around it early — {} output \
+                             renders the parts around an embed separately",
+                            self.embed_decls, mode
+                        ));
+                    }
+                    self.close_scopes(0);
+                }
                 self.parts.push(std::mem::take(&mut self.current_html));
                 self.embed_components.push(EmbedComponent {
                     index: self.embed_components.len() as u32,
@@ -61,8 +93,64 @@ impl TransformState {
         }
     }
 
+    /// Opens a wrapper for the heading at source `level`; it stays open for
+    /// the heading's scope.
+    fn open_scope(&mut self, level: u16, open: &str, close: &'static str) {
+        self.push_html(open);
+        self.scopes.push((level, close));
+    }
+
+    /// Records the scope of the heading at source `level`, which any wrappers
+    /// for it are opened inside.
+    fn enter_heading_scope(&mut self, level: u16) {
+        self.scopes.push((level, ""));
+    }
+
+    /// Closes every scope at `level` or deeper; `0` closes them all.
+    fn close_scopes(&mut self, level: u16) {
+        while let Some(&(open, close)) = self.scopes.last()
+            && open >= level
+        {
+            self.scopes.pop();
+            if !close.is_empty() {
+                self.push_html(close);
+            }
+        }
+    }
+
+    /// Applies a delimiter to the open wrappers, matching
+    /// [`delimiter_exits_heading_scope`]: a weak delimiter closes the innermost
+    /// heading's scope, a strong one every scope.
+    fn close_scopes_at(&mut self, delim: &DelimitingModifier) {
+        match delim {
+            DelimitingModifier::Weak => {
+                if let Some(&(innermost, _)) = self.scopes.last() {
+                    self.close_scopes(innermost);
+                }
+            }
+            DelimitingModifier::Strong => self.close_scopes(0),
+            DelimitingModifier::HorizontalRule => {}
+        }
+    }
+
     fn finalize(mut self) -> (Vec<String>, Vec<EmbedComponent>, String) {
+        self.close_scopes(0);
         self.parts.push(self.current_html);
+        // `@embed html` code is spliced into the page between parts, so it's
+        // as much final HTML as the parts are.
+        crate::options::with(|options| {
+            let Some(policy) = &options.sanitize else {
+                return;
+            };
+            for part in &mut self.parts {
+                *part = sanitize(part, policy);
+            }
+            for embed in &mut self.embed_components {
+                if embed.mode == OutputMode::html.as_str() {
+                    embed.code = sanitize(&embed.code, policy);
+                }
+            }
+        });
         (
             self.parts,
             self.embed_components,
@@ -75,7 +163,26 @@ pub fn transform(
     ast: &[NorgAST],
     mode: Option<OutputMode>,
 ) -> Result<(Vec<String>, Vec<EmbedComponent>, String), EmbedParseError> {
-    let mut state = TransformState::new(mode, document_ids(ast));
+    render(ast, ExampleSources::default(), mode)
+}
+
+/// Like [`transform`], with the source `ast` was parsed from, so `|example`
+/// tags can show their raw Norg next to the rendered output.
+pub fn transform_with_source(
+    ast: &[NorgAST],
+    source: &str,
+    mode: Option<OutputMode>,
+) -> Result<(Vec<String>, Vec<EmbedComponent>, String), EmbedParseError> {
+    render(ast, ExampleSources::new(ast, source), mode)
+}
+
+fn render(
+    ast: &[NorgAST],
+    examples: ExampleSources,
+    mode: Option<OutputMode>,
+) -> Result<(Vec<String>, Vec<EmbedComponent>, String), EmbedParseError> {
+    warn_invalid_id_prefix();
+    let mut state = TransformState::new(mode, document_ids(ast), examples);
     transform_nodes(ast, &mut state)?;
     // Leftovers mean this walk and the pre-pass disagreed about what's visible.
     debug_assert_eq!(
@@ -83,6 +190,10 @@ pub fn transform(
         (0, 0),
         "renderer and document_ids disagreed on visible nodes"
     );
+    debug_assert!(
+        state.examples.all_taken(),
+        "renderer and ExampleSources disagreed on rendered examples"
+    );
     Ok(state.finalize())
 }
 
@@ -94,13 +205,27 @@ fn transform_nodes(nodes: &[NorgAST], state: &mut TransformState) -> Result<(),
         // different marker type as a sibling `List`, not as item content).
         // List rendering is a pure function over the flattened run — it has
         // no access to the embed/css stream, so it cannot misalign it.
+        // An annotated list opens a run of its own, so its carryovers land on
+        // that list's container.
         let start = i;
         let mut events = Vec::new();
-        while i < nodes.len() && collect_list_items(&nodes[i], &mut events) {
+        let mut attrs = BlockAttrs::default();
+        if is_annotated_list(&nodes[i]) {
+            let target;
+            (attrs, target) = block_attrs(&nodes[i]);
+            attrs.warn_collapsed_ignored();
+            attrs.warn_image_options_ignored();
+            collect_list_items(target, &mut events);
+            i += 1;
+        }
+        while i < nodes.len()
+            && !is_annotated_list(&nodes[i])
+            && collect_list_items(&nodes[i], &mut events)
+        {
             i += 1;
         }
         if i > start {
-            state.push_list(&events);
+            state.push_list(&events, &attrs);
             continue;
         }
 
@@ -112,20 +237,26 @@ fn transform_nodes(nodes: &[NorgAST], state: &mut TransformState) -> Result<(),
             continue;
         }
 
-        transform_node(&nodes[i], state)?;
+        transform_node(&nodes[i], &BlockAttrs::default(), state)?;
         i += 1;
     }
     Ok(())
 }
 
-fn transform_node(node: &NorgAST, state: &mut TransformState) -> Result<(), EmbedParseError> {
+/// Renders one node. `attrs` come from the carryovers annotating it; only
+/// headings, paragraphs and HTML-producing verbatim tags can carry them.
+fn transform_node(
+    node: &NorgAST,
+    attrs: &BlockAttrs,
+    state: &mut TransformState,
+) -> Result<(), EmbedParseError> {
     match node {
         NorgAST::List { .. } | NorgAST::NestableDetachedModifier { .. } => {
             // `transform_nodes` consumes list runs before dispatching here.
             debug_assert!(false, "list nodes are consumed by transform_nodes");
             let mut events = Vec::new();
             collect_list_items(node, &mut events);
-            state.push_list(&events);
+            state.push_list(&events, attrs);
         }
         NorgAST::VerbatimRangedTag { name, .. } if is_comment_tag(name) => {}
         NorgAST::VerbatimRangedTag {
@@ -133,30 +264,26 @@ fn transform_node(node: &NorgAST, state: &mut TransformState) -> Result<(), Embe
             parameters,
             content,
             ..
-        } => {
-            let tag = VerbatimTag::from(name.as_slice());
-            // Capture the ordinal before incrementing; see `embed_decls` doc.
-            let embed_index = state.embed_decls;
-            if matches!(tag, VerbatimTag::Embed) {
-                state.embed_decls += 1;
-            }
-            if let Some(result) = tag.render(
-                parameters,
-                content,
-                state.mode,
-                &mut state.highlighter,
-                embed_index,
-            )? {
-                state.apply_verbatim(result);
-            }
-        }
+        } => transform_verbatim(name, parameters, content, attrs, false, state)?,
         NorgAST::Heading {
             level,
             title,
             content,
             ..
         } => {
-            let (title_html, id, tag_level) = heading_html_and_id(title, *level, &mut state.ids);
+            let HeadingParts {
+                html: title_html,
+                id,
+                level: tag_level,
+                number,
+            } = heading_parts(title, *level, &mut state.ids);
+            let title_html = match number {
+                Some(number) => {
+                    format!(r#"<span class="heading-number">{number}</span> {title_html}"#)
+                }
+                None => title_html,
+            };
+            let title_html = with_permalink(title_html, &id);
             // A symbol-only title (e.g. `* @@@`) slugs to "" — omit the
             // attribute rather than emit an HTML5-invalid `id=""`.
             let id_attr = if id.is_empty() {
@@ -164,13 +291,41 @@ fn transform_node(node: &NorgAST, state: &mut TransformState) -> Result<(), Embe
             } else {
                 format!(" id=\"{id}\"")
             };
-            state.push_html(&format!(
-                "<h{tag_level}{id_attr}>{title_html}</h{tag_level}>"
-            ));
+            // The id is left out: `+id` was already resolved by `DocumentIds`.
+            let extra = attrs.markup(false);
+            let (open, close) = heading_tag(tag_level);
+            let heading = format!("<{open}{id_attr}{extra}>{title_html}</{close}>");
+
+            state.close_scopes(*level);
+            state.enter_heading_scope(*level);
+            let (sections, collapse_level) = crate::options::with(|options| {
+                (options.sections.unwrap_or(false), options.collapse_level)
+            });
+            if sections {
+                let open = if id.is_empty() {
+                    "<section>".to_string()
+                } else {
+                    format!(r#"<section aria-labelledby="{id}">"#)
+                };
+                state.open_scope(*level, &open, "</section>");
+            }
+            // The id stays on the heading inside `<summary>`, so links to it
+            // still land on the (always visible) summary; the plugin's
+            // `virtual:norg-sections` script opens sections around deeper ones.
+            if attrs.collapsed || collapse_level.is_some_and(|min| u32::from(*level) >= min) {
+                state.open_scope(
+                    *level,
+                    &format!("<details><summary>{heading}</summary>"),
+                    "</details>",
+                );
+            } else {
+                state.push_html(&heading);
+            }
             transform_nodes(content, state)?;
         }
         NorgAST::Paragraph(segments) => {
-            if let Some(html) = paragraph(segments, &state.ids) {
+            if let Some(mut html) = paragraph(segments, &state.ids) {
+                attrs.inject(&mut html);
                 state.push_html(&html);
             }
         }
@@ -180,30 +335,374 @@ fn transform_node(node: &NorgAST, state: &mut TransformState) -> Result<(), Embe
             content,
             ..
         } => {
-            let html = rangeable_modifier(modifier_type, title, content, &mut state.ids);
+            warn_attributes_ignored(attrs);
+            let html = rangeable_modifier(modifier_type, title, content, &mut state.ids, true);
             state.push_html(&html);
         }
-        NorgAST::DelimitingModifier(delim) => state.push_html(delimiter(delim)),
-        NorgAST::CarryoverTag {
+        NorgAST::DelimitingModifier(delim) => {
+            warn_attributes_ignored(attrs);
+            state.close_scopes_at(delim);
+            state.push_html(delimiter(delim));
+        }
+        NorgAST::CarryoverTag { .. } => {
+            if comment_target(node).is_none() {
+                let (attrs, target) = block_attrs(node);
+                if !matches!(target, NorgAST::Heading { .. }) {
+                    attrs.warn_collapsed_ignored();
+                }
+                if !matches!(
+                    target,
+                    NorgAST::VerbatimRangedTag { .. } | NorgAST::InfirmTag { .. }
+                ) {
+                    attrs.warn_image_options_ignored();
+                }
+                transform_node(target, &attrs, state)?;
+            }
+        }
+        NorgAST::RangedTag { name, .. } if is_comment_tag(name) => {}
+        NorgAST::RangedTag {
+            name,
+            parameters,
+            content,
+        } => {
+            warn_attributes_ignored(attrs);
+            transform_ranged(name, parameters, content, state)?;
+        }
+        NorgAST::InfirmTag { name, parameters } => {
+            transform_infirm(name, parameters, attrs, false, state)?
+        }
+    }
+    Ok(())
+}
+
+/// Renders a verbatim tag. Inside a ranged tag (`nested`) an `@embed` can't be
+/// split out into its own part without breaking the wrapper, so it's skipped.
+fn transform_verbatim(
+    name: &[String],
+    parameters: &[String],
+    content: &str,
+    attrs: &BlockAttrs,
+    nested: bool,
+    state: &mut TransformState,
+) -> Result<(), EmbedParseError> {
+    let tag = VerbatimTag::from(name);
+    // Capture the ordinal before incrementing; see `embed_decls` doc.
+    let embed_index = state.embed_decls;
+    if matches!(tag, VerbatimTag::Embed) {
+        state.embed_decls += 1;
+    }
+    let parameters = if matches!(tag, VerbatimTag::Image | VerbatimTag::ImageGallery) {
+        attrs.image_parameters(parameters)
+    } else {
+        attrs.warn_image_options_ignored();
+        parameters.to_vec()
+    };
+    let Some(result) = tag.render(
+        &parameters,
+        content,
+        state.mode,
+        &mut state.highlighter,
+        &state.ids,
+        embed_index,
+    )?
+    else {
+        return Ok(());
+    };
+    match result {
+        VerbatimTagResult::Html(mut html) => {
+            attrs.inject(&mut html);
+            state.apply_verbatim(VerbatimTagResult::Html(html));
+        }
+        VerbatimTagResult::Embed { .. } if nested => crate::diagnostics::warn(format!(
+            "embed #{} is inside a ranged tag, which can't hold components — skipped",
+            embed_index + 1
+        )),
+        result => {
+            warn_attributes_ignored(attrs);
+            state.apply_verbatim(result);
+        }
+    }
+    Ok(())
+}
+
+/// Renders a `.name …` tag. Images and media are the verbatim forms with an
+/// empty body, so they go through that path — URL checks and carryovers
+/// included.
+fn transform_infirm(
+    name: &[String],
+    parameters: &[String],
+    attrs: &BlockAttrs,
+    nested: bool,
+    state: &mut TransformState,
+) -> Result<(), EmbedParseError> {
+    match InfirmTag::from(name) {
+        InfirmTag::Image | InfirmTag::Video | InfirmTag::Audio => {
+            transform_verbatim(name, parameters, "", attrs, nested, state)
+        }
+        InfirmTag::Unknown => {
+            warn_unimplemented("infirm", name);
+            Ok(())
+        }
+    }
+}
+
+/// Renders a `|name … |end` tag and, recursively, the blocks inside it.
+fn transform_ranged(
+    name: &[String],
+    parameters: &[String],
+    content: &[NorgASTFlat],
+    state: &mut TransformState,
+) -> Result<(), EmbedParseError> {
+    match RangedTag::from(name) {
+        RangedTag::Details => {
+            let summary = if parameters.is_empty() {
+                "Details".to_string()
+            } else {
+                encode_minimal(&parameters.join(" "))
+            };
+            state.push_html(&format!("<details><summary>{summary}</summary>"));
+            transform_flat(content, state)?;
+            state.push_html("</details>");
+        }
+        RangedTag::Group => {
+            state.push_html(r#"<div class="group">"#);
+            transform_flat(content, state)?;
+            state.push_html("</div>");
+        }
+        RangedTag::CodeGroup => transform_code_group(parameters, content, state)?,
+        RangedTag::Example => {
+            state.push_html(r#"<div class="example">"#);
+            if let Some(source) = state.examples.next_source() {
+                let source = format!(
+                    r#"<pre class="example-source"><code>{}</code></pre>"#,
+                    encode_minimal(source)
+                );
+                state.push_html(&source);
+            }
+            state.push_html(r#"<div class="example-output">"#);
+            transform_flat(content, state)?;
+            state.push_html("</div>");
+            state.push_html("</div>");
+        }
+        RangedTag::Callout(kind) => {
+            state.push_html(&format!(
+                r#"<aside class="callout callout-{}" role="note">"#,
+                encode_minimal(&kind)
+            ));
+            if !parameters.is_empty() {
+                state.push_html(&format!(
+                    r#"<p class="callout-title">{}</p>"#,
+                    encode_minimal(&parameters.join(" "))
+                ));
+            }
+            transform_flat(content, state)?;
+            state.push_html("</aside>");
+        }
+        RangedTag::Unknown => warn_unimplemented("ranged", name),
+    }
+    Ok(())
+}
+
+/// Renders a `|code-group` as a tab set: a `tablist` with one tab per `@code`
+/// block, then a `tabpanel` per block. The tab list starts `hidden`, so
+/// without a script to switch tabs the blocks read as a plain stack.
+fn transform_code_group(
+    parameters: &[String],
+    content: &[NorgASTFlat],
+    state: &mut TransformState,
+) -> Result<(), EmbedParseError> {
+    let blocks: Vec<(&NorgASTFlat, String)> = content
+        .iter()
+        .filter_map(|node| match flat_carryover_target(node) {
+            NorgASTFlat::VerbatimRangedTag {
+                name, parameters, ..
+            } if matches!(VerbatimTag::from(name.as_slice()), VerbatimTag::Code) => {
+                Some((node, code_label(parameters)))
+            }
+            NorgASTFlat::RangedTag { name, .. } | NorgASTFlat::VerbatimRangedTag { name, .. }
+                if is_comment_tag(name) =>
+            {
+                None
+            }
+            _ => {
+                crate::diagnostics::warn(
+                    "code-group holds only @code blocks — other content skipped",
+                );
+                None
+            }
+        })
+        .collect();
+
+    state.code_groups += 1;
+    let group = state.code_groups;
+    let prefix = id_prefix();
+    let id = |role: &str, index: usize| {
+        encode_minimal(&format!("{prefix}code-group-{group}-{role}-{index}"))
+    };
+    let label = if parameters.is_empty() {
+        String::new()
+    } else {
+        format!(r#" aria-label="{}""#, encode_minimal(&parameters.join(" ")))
+    };
+
+    let mut tabs = format!(r#"<div class="code-group"><div role="tablist"{label} hidden>"#);
+    for (index, (_, text)) in blocks.iter().enumerate() {
+        let selected = index == 0;
+        let _ = write!(
+            tabs,
+            r#"<button type="button" role="tab" id="{}" aria-controls="{}" aria-selected="{selected}" tabindex="{}">{}</button>"#,
+            id("tab", index),
+            id("panel", index),
+            if selected { 0 } else { -1 },
+            encode_minimal(text)
+        );
+    }
+    tabs.push_str("</div>");
+    state.push_html(&tabs);
+    for (index, (node, _)) in blocks.into_iter().enumerate() {
+        let (panel, tab) = (id("panel", index), id("tab", index));
+        state.push_html(&format!(
+            r#"<div role="tabpanel" id="{panel}" aria-labelledby="{tab}">"#
+        ));
+        transform_flat_node(node, state)?;
+        state.push_html("</div>");
+    }
+    state.push_html("</div>");
+    Ok(())
+}
+
+/// Renders the flat blocks inside a ranged tag. They sit outside the
+/// document's heading tree, so headings and footnotes here get no ids.
+fn transform_flat(
+    content: &[NorgASTFlat],
+    state: &mut TransformState,
+) -> Result<(), EmbedParseError> {
+    let mut index = 0;
+    while index < content.len() {
+        if let Some(next) = flat_comment_end(content, index) {
+            index = next;
+            continue;
+        }
+
+        let start = index;
+        let mut events = Vec::new();
+        while index < content.len() && collect_flat_list_item(&content[index], &mut events) {
+            index += 1;
+        }
+        if index > start {
+            state.push_list(&events, &BlockAttrs::default());
+            continue;
+        }
+
+        transform_flat_node(&content[index], state)?;
+        index += 1;
+    }
+    Ok(())
+}
+
+fn transform_flat_node(
+    node: &NorgASTFlat,
+    state: &mut TransformState,
+) -> Result<(), EmbedParseError> {
+    match node {
+        NorgASTFlat::Paragraph(segments) => {
+            if let Some(html) = paragraph(segments, &state.ids) {
+                state.push_html(&html);
+            }
+        }
+        NorgASTFlat::NestableDetachedModifier { .. } => {
+            // `transform_flat` consumes list runs before dispatching here.
+            let mut events = Vec::new();
+            collect_flat_list_item(node, &mut events);
+            state.push_list(&events, &BlockAttrs::default());
+        }
+        NorgASTFlat::Heading { level, title, .. } => {
+            let (open, close) = heading_tag(output_level(*level));
+            let title_html = convert_segments_with_ids(title, &state.ids);
+            state.push_html(&format!("<{open}>{title_html}</{close}>"));
+        }
+        NorgASTFlat::RangeableDetachedModifier {
+            modifier_type,
+            title,
+            content,
+            ..
+        } => {
+            let html = rangeable_modifier(modifier_type, title, content, &mut state.ids, false);
+            state.push_html(&html);
+        }
+        NorgASTFlat::VerbatimRangedTag { name, .. } if is_comment_tag(name) => {}
+        NorgASTFlat::VerbatimRangedTag {
+            name,
+            parameters,
+            content,
+        } => transform_verbatim(
+            name,
+            parameters,
+            content,
+            &BlockAttrs::default(),
+            true,
+            state,
+        )?,
+        NorgASTFlat::RangedTag { name, .. } if is_comment_tag(name) => {}
+        NorgASTFlat::RangedTag {
+            name,
+            parameters,
+            content,
+        } => transform_ranged(name, parameters, content, state)?,
+        NorgASTFlat::CarryoverTag {
             name, next_object, ..
         } => {
-            if comment_target(node).is_none() {
+            if !is_tangle_tag(name) {
                 warn_carryover_ignored(name);
-                transform_nodes(std::slice::from_ref(next_object), state)?;
             }
+            transform_flat_node(next_object, state)?;
+        }
+        NorgASTFlat::DelimitingModifier(delim) => state.push_html(delimiter(delim)),
+        NorgASTFlat::InfirmTag { name, parameters } => {
+            transform_infirm(name, parameters, &BlockAttrs::default(), true, state)?
         }
-        NorgAST::RangedTag { name, .. } if is_comment_tag(name) => {}
-        NorgAST::RangedTag { name, .. } => warn_unimplemented("ranged", name),
-        NorgAST::InfirmTag { name, .. } => warn_unimplemented("infirm", name),
     }
     Ok(())
 }
 
+/// Opening and closing tag names for a heading at an output level. HTML stops
+/// at `<h6>`; deeper levels only get here under the `aria` overflow policy.
+fn heading_tag(level: u16) -> (String, String) {
+    if level <= 6 {
+        (format!("h{level}"), format!("h{level}"))
+    } else {
+        (
+            format!(r#"div role="heading" aria-level="{level}""#),
+            "div".to_string(),
+        )
+    }
+}
+
+/// Adds the configured permalink to a heading's title. A heading without an id
+/// has nothing to link to and gets none.
+fn with_permalink(title_html: String, id: &str) -> String {
+    crate::options::with(|options| {
+        let Some(permalink) = options.permalink.as_ref().filter(|_| !id.is_empty()) else {
+            return title_html;
+        };
+        let symbol = encode_minimal(permalink.symbol.as_deref().unwrap_or("#"));
+        let anchor = format!(
+            r##"<a class="heading-anchor" href="#{id}" aria-label="Permalink">{symbol}</a>"##
+        );
+        match permalink.position.unwrap_or(PermalinkPosition::after) {
+            PermalinkPosition::before => format!("{anchor} {title_html}"),
+            PermalinkPosition::after => format!("{title_html} {anchor}"),
+        }
+    })
+}
+
 fn rangeable_modifier(
     modifier_type: &RangeableDetachedModifier,
     title: &[ParagraphSegment],
     content: &[NorgASTFlat],
     ids: &mut DocumentIds,
+    // Footnotes outside the heading tree (in a ranged tag) have no reserved id.
+    anchored: bool,
 ) -> String {
     // convert_segments output is final HTML (text already escaped, markup
     // intentional) — re-encoding it would render `&` as `&amp;` and inline
@@ -216,10 +715,14 @@ fn rangeable_modifier(
             format!("<dl><dt>{title_html}</dt><dd>{body}</dd></dl>")
         }
         RangeableDetachedModifier::Footnote => {
-            let id = ids.next_footnote();
+            let id_attr = if anchored {
+                format!(" id=\"{}\"", ids.next_footnote())
+            } else {
+                String::new()
+            };
             // `body` is already a sequence of <p> blocks.
             format!(
-                "<aside id=\"{id}\" class=\"footnote\"><strong>{title_html}</strong>{body}</aside>"
+                "<aside{id_attr} class=\"footnote\"><strong>{title_html}</strong>{body}</aside>"
             )
         }
         RangeableDetachedModifier::Table => {
@@ -257,60 +760,3 @@ fn rangeable_body(content: &[NorgASTFlat], ids: &DocumentIds) -> String {
     }
     body
 }
-
-fn flat_comment_end(content: &[NorgASTFlat], index: usize) -> Option<usize> {
-    let (kind, target) = flat_comment_target(&content[index])?;
-
-    if let Some(level) = flat_heading_level(target) {
-        let mut next = index + 1;
-        let mut current_level = level as i16;
-        while next < content.len() {
-            match flat_heading_level(&content[next]) {
-                Some(next_level) if next_level <= level || kind == CommentKind::Weak => break,
-                Some(next_level) => current_level = next_level as i16,
-                None if matches!(
-                    &content[next],
-                    NorgASTFlat::DelimitingModifier(delim)
-                        if delimiter_exits_heading_scope(
-                            delim,
-                            level,
-                            &mut current_level,
-                        )
-                ) =>
-                {
-                    break;
-                }
-                None => {}
-            }
-            next += 1;
-        }
-        return Some(next);
-    }
-
-    if kind == CommentKind::Strong
-        && let NorgASTFlat::NestableDetachedModifier {
-            modifier_type,
-            level,
-            ..
-        } = target
-    {
-        let mut next = index + 1;
-        while next < content.len() {
-            let NorgASTFlat::NestableDetachedModifier {
-                modifier_type: next_type,
-                level: next_level,
-                ..
-            } = flat_carryover_target(&content[next])
-            else {
-                break;
-            };
-            if next_level < level || (next_level == level && next_type != modifier_type) {
-                break;
-            }
-            next += 1;
-        }
-        return Some(next);
-    }
-
-    Some(index + 1)
-}
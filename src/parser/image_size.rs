@@ -0,0 +1,230 @@
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// How much of an SVG is searched for its root `<svg>` tag.
+const SVG_SCAN_LIMIT: u64 = 64 * 1024;
+
+/// The intrinsic pixel size of a PNG, JPEG, GIF, WebP or SVG file, read from
+/// its header without decoding the image. `Ok(None)` for any other format, a
+/// truncated header, or an SVG sized only in relative units.
+pub fn image_size(path: &Path) -> io::Result<Option<(u32, u32)>> {
+    size_of(&mut BufReader::new(File::open(path)?))
+}
+
+fn size_of(reader: &mut (impl Read + Seek)) -> io::Result<Option<(u32, u32)>> {
+    let mut head = [0; 32];
+    let head = &head[..read_up_to(reader, &mut head)?];
+
+    let size = if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png(head)
+    } else if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+        gif(head)
+    } else if head.starts_with(b"RIFF") && head.get(8..12) == Some(&b"WEBP"[..]) {
+        webp(head)
+    } else if head.starts_with(&[0xFF, 0xD8]) {
+        match jpeg(reader) {
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => None,
+            size => size?,
+        }
+    } else if head.trim_ascii_start().starts_with(b"<") || head.starts_with("\u{feff}".as_bytes()) {
+        svg(reader)?
+    } else {
+        None
+    };
+    Ok(size.filter(|&(width, height)| width > 0 && height > 0))
+}
+
+fn read_up_to(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+fn u16_be(bytes: &[u8]) -> u32 {
+    u16::from_be_bytes([bytes[0], bytes[1]]).into()
+}
+
+fn u16_le(bytes: &[u8]) -> u32 {
+    u16::from_le_bytes([bytes[0], bytes[1]]).into()
+}
+
+fn u24_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0])
+}
+
+/// The `IHDR` chunk always comes first.
+fn png(head: &[u8]) -> Option<(u32, u32)> {
+    let ihdr = head.get(16..24)?;
+    Some((
+        u32::from_be_bytes(ihdr[..4].try_into().ok()?),
+        u32::from_be_bytes(ihdr[4..].try_into().ok()?),
+    ))
+}
+
+/// The logical screen size from the header.
+fn gif(head: &[u8]) -> Option<(u32, u32)> {
+    let screen = head.get(6..10)?;
+    Some((u16_le(&screen[..2]), u16_le(&screen[2..])))
+}
+
+/// Lossy (`VP8 `), lossless (`VP8L`) and extended (`VP8X`) files each keep
+/// the size in a different place in their first chunk.
+fn webp(head: &[u8]) -> Option<(u32, u32)> {
+    let chunk = head.get(20..30)?;
+    match &head[12..16] {
+        b"VP8 " => Some((
+            u16_le(&chunk[6..8]) & 0x3FFF,
+            u16_le(&chunk[8..10]) & 0x3FFF,
+        )),
+        b"VP8L" => {
+            let bits = u32::from_le_bytes(chunk[1..5].try_into().ok()?);
+            Some((1 + (bits & 0x3FFF), 1 + ((bits >> 14) & 0x3FFF)))
+        }
+        b"VP8X" => Some((1 + u24_le(&chunk[4..7]), 1 + u24_le(&chunk[7..10]))),
+        _ => None,
+    }
+}
+
+/// Walks the marker segments to the first start-of-frame, skipping EXIF and
+/// other metadata by their lengths rather than reading them.
+fn jpeg(reader: &mut (impl Read + Seek)) -> io::Result<Option<(u32, u32)>> {
+    reader.seek(SeekFrom::Start(2))?;
+    let mut byte = [0; 1];
+    loop {
+        reader.read_exact(&mut byte)?;
+        if byte[0] != 0xFF {
+            return Ok(None);
+        }
+        // Any number of 0xFF fill bytes may precede the marker.
+        while byte[0] == 0xFF {
+            reader.read_exact(&mut byte)?;
+        }
+        match byte[0] {
+            // Standalone markers carry no length.
+            0x01 | 0xD0..=0xD8 => continue,
+            // End of image, or scan data, before any frame.
+            0xD9 | 0xDA => return Ok(None),
+            _ => {}
+        }
+        let mut length = [0; 2];
+        reader.read_exact(&mut length)?;
+        let length = u16_be(&length);
+        // SOF0–SOF15, except DHT, JPG and DAC which share the range.
+        if matches!(byte[0], 0xC0..=0xCF) && !matches!(byte[0], 0xC4 | 0xC8 | 0xCC) {
+            let mut frame = [0; 5];
+            reader.read_exact(&mut frame)?;
+            return Ok(Some((u16_be(&frame[3..]), u16_be(&frame[1..3]))));
+        }
+        reader.seek(SeekFrom::Current(i64::from(length) - 2))?;
+    }
+}
+
+/// The root element's `width`/`height` in pixels, or the `viewBox` size —
+/// scaled to keep its aspect ratio when only one of them is given.
+fn svg(reader: &mut (impl Read + Seek)) -> io::Result<Option<(u32, u32)>> {
+    reader.seek(SeekFrom::Start(0))?;
+    let mut text = Vec::new();
+    reader.take(SVG_SCAN_LIMIT).read_to_end(&mut text)?;
+    let text = String::from_utf8_lossy(&text);
+
+    let Some(start) = text
+        .match_indices("<svg")
+        .map(|(start, _)| start)
+        .find(|&start| text[start + 4..].starts_with(|c: char| c.is_whitespace() || c == '>'))
+    else {
+        return Ok(None);
+    };
+    let tag = &text[start..];
+    let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+
+    let width = attribute(tag, "width").and_then(pixels);
+    let height = attribute(tag, "height").and_then(pixels);
+    let view_box = attribute(tag, "viewBox").and_then(|view_box| {
+        let values: Vec<f64> = view_box
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|value| !value.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        match values[..] {
+            [_, _, width, height] if width > 0.0 && height > 0.0 => Some((width, height)),
+            _ => None,
+        }
+    });
+
+    let size = match (width, height, view_box) {
+        (Some(width), Some(height), _) => Some((width, height)),
+        (Some(width), None, Some((w, h))) => Some((width, width * h / w)),
+        (None, Some(height), Some((w, h))) => Some((height * w / h, height)),
+        (None, None, view_box) => view_box,
+        _ => None,
+    };
+    Ok(size.map(|(width, height)| (width.round() as u32, height.round() as u32)))
+}
+
+/// The value of `name="…"` (or single-quoted) within a start tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(index) = rest.find(name) {
+        let preceded = rest[..index].ends_with(|c: char| c.is_whitespace());
+        rest = &rest[index + name.len()..];
+        let Some(value) = rest.trim_start().strip_prefix('=') else {
+            continue;
+        };
+        if !preceded {
+            continue;
+        }
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+        let value = &value[1..];
+        return value.find(quote).map(|end| &value[..end]);
+    }
+    None
+}
+
+/// A length in pixels; `%`, `em` and the like depend on where the image lands.
+fn pixels(length: &str) -> Option<f64> {
+    let length = length.trim();
+    let number = length.strip_suffix("px").unwrap_or(length);
+    number.parse().ok().filter(|value: &f64| *value > 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn size(bytes: &[u8]) -> Option<(u32, u32)> {
+        size_of(&mut Cursor::new(bytes)).expect("in-memory read")
+    }
+
+    #[test]
+    fn reads_each_format_header() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend([0, 0, 0x03, 0x20, 0, 0, 0x01, 0xC2]);
+        assert_eq!(size(&png), Some((800, 450)));
+
+        assert_eq!(size(b"GIF89a\x40\x01\xF0\x00\0\0\0\0"), Some((320, 240)));
+
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1, 0x00, 0x04, 0xAA, 0xBB];
+        jpeg.extend([0xFF, 0xC0, 0x00, 0x11, 0x08, 0x02, 0x58, 0x03, 0x20]);
+        assert_eq!(size(&jpeg), Some((800, 600)));
+        assert_eq!(size(&jpeg[..10]), None);
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0".to_vec();
+        webp.extend([0x1F, 0x03, 0x00, 0xC1, 0x01, 0x00]);
+        assert_eq!(size(&webp), Some((800, 450)));
+
+        let svg = br#"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg" width="200px" viewBox="0 0 100 50">"#;
+        assert_eq!(size(svg), Some((200, 100)));
+        assert_eq!(size(br#"<svg width="100%" height="100%">"#), None);
+
+        assert_eq!(size(b"BM not supported"), None);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::utils::document_relative;
+use std::cell::RefCell;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+thread_local! {
+    static SINK: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// The text of `path`, resolved against the document's directory. Files
+/// outside the include root, and anything but regular files, are refused. The file is recorded as a dependency
+/// even when it can't be read, so creating it later re-renders the document.
+pub fn read(path: &str) -> Option<String> {
+    let Some(resolved) = document_relative(path) else {
+        crate::diagnostics::warn(format!(
+            "cannot include {path}: the document's file path is unknown (set filePath)"
+        ));
+        return None;
+    };
+    let root = include_root();
+    if !is_within(&resolved, &root) {
+        crate::diagnostics::warn(format!(
+            "refusing to include {path}: it is outside {} (set includeRoot to allow it)",
+            root.display()
+        ));
+        return None;
+    }
+    if std::fs::metadata(&resolved).is_ok_and(|metadata| !metadata.is_file()) {
+        crate::diagnostics::warn(format!("cannot include {path}: not a file"));
+        return None;
+    }
+    let dependency = resolved.to_string_lossy().into_owned();
+    SINK.with(|sink| {
+        if let Some(files) = sink.borrow_mut().as_mut()
+            && !files.contains(&dependency)
+        {
+            files.push(dependency);
+        }
+    });
+    match std::fs::read_to_string(&resolved) {
+        Ok(text) => Some(text),
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            crate::diagnostics::warn(format!(
+                "included file not found: {path} (looked for {})",
+                resolved.display()
+            ));
+            None
+        }
+        Err(error) => {
+            crate::diagnostics::warn(format!("could not read included file {path}: {error}"));
+            None
+        }
+    }
+}
+
+/// The directory files read from disk must stay within: the `include_root`
+/// option, or else the document's own directory.
+pub fn include_root() -> PathBuf {
+    let root = crate::options::with(|options| options.include_root.clone())
+        .map(PathBuf::from)
+        .or_else(|| document_relative(""))
+        .unwrap_or_default();
+    if root.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        root
+    }
+}
+
+/// Whether `file` lies under `root` once both are canonical.
+pub fn is_within(file: &Path, root: &Path) -> bool {
+    match (canonical(file), canonical(root)) {
+        (Some(file), Some(root)) => file.starts_with(root),
+        _ => false,
+    }
+}
+
+/// `path` with symlinks and `..` resolved. A file that doesn't exist yet is
+/// placed under its nearest existing ancestor; `None` if even that fails.
+fn canonical(path: &Path) -> Option<PathBuf> {
+    path.canonicalize()
+        .ok()
+        .or_else(|| Some(canonical(path.parent()?)?.join(path.file_name()?)))
+}
+
+/// Collects the files read while `run` renders, in the order first read.
+pub fn capture<T>(run: impl FnOnce() -> T) -> (T, Vec<String>) {
+    let previous = SINK.with(|sink| sink.replace(Some(Vec::new())));
+    let value = run();
+    let files = SINK.with(|sink| sink.replace(previous)).unwrap_or_default();
+    (value, files)
+}
@@ -1,22 +1,34 @@
+mod assets;
 mod ast_handlers;
 mod diagnostics;
 mod html;
+mod image_size;
+mod includes;
+mod math;
 mod metadata;
+mod options;
+mod sanitize;
 mod segments;
+mod tangle;
 mod toc;
 mod types;
 mod utils;
 
-pub use html::transform;
+pub use html::{transform, transform_with_source};
 pub use metadata::extract_metadata;
+pub use tangle::tangle;
 pub use toc::extract_toc;
-pub use types::{EmbedComponent, OutputMode, TocEntry};
-pub use utils::into_slug;
+pub use types::{
+    AssetKind, AssetReference, EmbedComponent, HeadingOverflow, OutputMode, ParseOptions,
+    PermalinkOptions, PermalinkPosition, SanitizeOptions, SlugStyle, TocEntry,
+};
+pub use utils::{into_slug, slugify};
 
 use arborium::theme::builtin;
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
 use serde_json::{Map, Value};
+use std::collections::HashMap;
 
 #[cfg(not(target_arch = "wasm32"))]
 const PARSER_STACK_SIZE: usize = 32 * 1024 * 1024;
@@ -31,13 +43,51 @@ pub struct NorgParseResult {
     /// Non-fatal warnings from rendering (skipped/altered content), for the
     /// host to surface — stderr is invisible in a Vite worker.
     pub diagnostics: Option<Vec<String>>,
+    /// Local files referenced by placeholder in the HTML; empty unless
+    /// `asset_placeholders` is set.
+    pub assets: Vec<AssetReference>,
+    /// Files read into the output (`@code file=…` includes), for the host to
+    /// watch.
+    pub dependencies: Vec<String>,
+}
+
+/// The files a document's `@code` blocks tangle into.
+#[napi(object)]
+pub struct TangleResult {
+    /// File contents by target path, as written in the document.
+    pub files: HashMap<String, String>,
+    /// Blocks with conflicting or missing targets, and other skipped tangles.
+    pub diagnostics: Vec<String>,
 }
 
 #[napi]
-pub fn parse_norg(content: String, mode: Option<String>) -> Result<NorgParseResult> {
+pub fn parse_norg(
+    content: String,
+    mode: Option<String>,
+    options: Option<ParseOptions>,
+) -> Result<NorgParseResult> {
+    let options = options.unwrap_or_default();
+    on_parser_stack(move || {
+        options::scoped(options, || parse_norg_inner(&content, mode.as_deref()))
+    })
+}
+
+#[napi]
+pub fn tangle_norg(content: String) -> Result<TangleResult> {
+    on_parser_stack(move || {
+        let ast = rust_norg::parse_tree(&content).map_err(|e| format!("Parse error: {e:?}"))?;
+        let (files, diagnostics) = diagnostics::capture(|| tangle(&ast));
+        Ok(TangleResult { files, diagnostics })
+    })
+}
+
+/// Runs `run` on a stack deep enough for nested documents.
+fn on_parser_stack<T: Send + 'static>(
+    run: impl FnOnce() -> std::result::Result<T, String> + Send + 'static,
+) -> Result<T> {
     #[cfg(target_arch = "wasm32")]
     {
-        parse_norg_inner(&content, mode.as_deref()).map_err(Error::from_reason)
+        run().map_err(Error::from_reason)
     }
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -47,7 +97,7 @@ pub fn parse_norg(content: String, mode: Option<String>) -> Result<NorgParseResu
         let handle = std::thread::Builder::new()
             .name("norg-parse".into())
             .stack_size(PARSER_STACK_SIZE)
-            .spawn(move || parse_norg_inner(&content, mode.as_deref()))
+            .spawn(run)
             .map_err(|e| Error::from_reason(format!("Failed to spawn parser thread: {e}")))?;
 
         match handle.join() {
@@ -64,10 +114,21 @@ fn parse_norg_inner(
     let ast = rust_norg::parse_tree(content).map_err(|e| format!("Parse error: {e:?}"))?;
 
     let output_mode = mode.and_then(|s| s.parse().ok());
-    let (rendered, diagnostics) = diagnostics::capture(|| transform(&ast, output_mode));
+    let (((rendered, diagnostics), mut assets), dependencies) = includes::capture(|| {
+        assets::capture(|| {
+            diagnostics::capture(|| transform_with_source(&ast, content, output_mode))
+        })
+    });
     let toc = diagnostics::discard(|| extract_toc(&ast));
     let (html_parts, embed_components, embed_css) =
         rendered.map_err(|err| format_embed_error(&err))?;
+    for asset in &mut assets {
+        let part = html_parts
+            .iter()
+            .position(|part| part.contains(&asset.placeholder))
+            .unwrap_or_default();
+        asset.part = part as u32;
+    }
     let metadata = extract_metadata(&ast);
 
     Ok(NorgParseResult {
@@ -77,6 +138,8 @@ fn parse_norg_inner(
         embed_components,
         embed_css,
         diagnostics: Some(diagnostics),
+        assets,
+        dependencies,
     })
 }
 
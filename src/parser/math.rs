@@ -0,0 +1,813 @@
+use htmlescape::encode_minimal;
+
+/// Converts TeX math to MathML: inline for `$…$`, `display` for `@math`
+/// blocks. Covers the everyday subset — scripts, fractions, roots, Greek and
+/// operator symbols, function names, font variants, `\left…\right` fences,
+/// accents and matrix-style environments. Anything else is reported and
+/// rendered as `<merror>`, so the rest of the expression still shows.
+pub fn to_mathml(tex: &str, display: bool) -> String {
+    let mut parser = Parser {
+        src: tex,
+        pos: 0,
+        variant: None,
+        depth: 0,
+    };
+    let mut nodes = parser.parse_row();
+    // `parse_row` only stops early on a closer or separator with nothing
+    // open to claim it.
+    while parser.pos < parser.src.len() {
+        parser.skip_stray();
+        nodes.extend(parser.parse_row());
+    }
+    let display = if display { r#" display="block""# } else { "" };
+    format!("<math{display}>{}</math>", row(nodes))
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    /// `mathvariant` for identifiers inside `\mathbf{…}` and friends.
+    variant: Option<&'static str>,
+    /// Operands being parsed, one inside another.
+    depth: usize,
+}
+
+/// How deeply operands may nest before the rest of a group is skipped, well
+/// short of overflowing the stack.
+const MAX_DEPTH: usize = 128;
+
+/// A parsed operand, and whether scripts on it go above and below (`\sum`,
+/// `\lim`) rather than to the side.
+struct Atom {
+    mathml: String,
+    limits: bool,
+}
+
+impl Atom {
+    fn new(mathml: String) -> Self {
+        Self {
+            mathml,
+            limits: false,
+        }
+    }
+}
+
+enum Symbol {
+    Ident(&'static str),
+    Op(&'static str),
+    /// Operators taking their scripts as limits.
+    LargeOp(&'static str),
+    /// Upright function names; `limits` for `\lim`-like ones.
+    Func(&'static str, bool),
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    /// Whether the input continues with the control word `name` (and not a
+    /// longer one it prefixes, like `\rightarrow` for `\right`).
+    fn at_command(&self, name: &str) -> bool {
+        self.rest()
+            .strip_prefix('\\')
+            .and_then(|rest| rest.strip_prefix(name))
+            .is_some_and(|after| !after.starts_with(|c: char| c.is_ascii_alphabetic()))
+    }
+
+    /// Tokens that end the current row for an enclosing construct to claim.
+    fn at_row_end(&self) -> bool {
+        matches!(self.peek(), None | Some('}' | '&'))
+            || self.rest().starts_with(r"\\")
+            || self.at_command("right")
+            || self.at_command("end")
+    }
+
+    fn parse_row(&mut self) -> Vec<String> {
+        let mut nodes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.at_row_end() {
+                return nodes;
+            }
+            if let Some(atom) = self.parse_atom() {
+                nodes.push(self.parse_scripts(atom));
+            }
+        }
+    }
+
+    /// Consumes a row terminator that nothing open claims.
+    fn skip_stray(&mut self) {
+        let token = if self.rest().starts_with(r"\\") {
+            self.pos += 2;
+            r"\\".to_string()
+        } else if self.peek() == Some('\\') {
+            self.bump();
+            let name = self.command_name();
+            if name == "end" {
+                self.raw_group();
+            }
+            format!(r"\{name}")
+        } else {
+            self.bump().map(String::from).unwrap_or_default()
+        };
+        crate::diagnostics::warn(format!("math: unexpected '{token}' — skipped"));
+    }
+
+    fn command_name(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.bump();
+        }
+        if self.pos == start {
+            self.bump();
+        }
+        self.src[start..self.pos].to_string()
+    }
+
+    /// One operand; `None` for tokens that render nothing (`\displaystyle`).
+    /// Past [`MAX_DEPTH`] the rest of the enclosing group is reported and
+    /// skipped instead.
+    fn parse_atom(&mut self) -> Option<Atom> {
+        if self.depth == MAX_DEPTH {
+            crate::diagnostics::warn(format!(
+                "math: nested more than {MAX_DEPTH} levels deep — rest of the group skipped"
+            ));
+            self.skip_group();
+            return Some(Atom::new("<merror><mtext>…</mtext></merror>".to_string()));
+        }
+        self.depth += 1;
+        let atom = self.atom();
+        self.depth -= 1;
+        atom
+    }
+
+    /// Skips to the `}` closing the current group, or the end of the input.
+    fn skip_group(&mut self) {
+        let mut depth = 0;
+        while let Some(c) = self.peek() {
+            match c {
+                '\\' => {
+                    self.bump();
+                }
+                '{' => depth += 1,
+                '}' if depth == 0 => return,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            self.bump();
+        }
+    }
+
+    fn atom(&mut self) -> Option<Atom> {
+        let c = self.peek()?;
+        if c == '_' || c == '^' {
+            // A script with no base (`^2`, `{}^2`): attach it to an empty row.
+            return Some(Atom::new("<mrow></mrow>".to_string()));
+        }
+        self.bump();
+        let mathml = match c {
+            '{' => {
+                let nodes = self.parse_row();
+                self.close_group();
+                format!("<mrow>{}</mrow>", nodes.concat())
+            }
+            '\\' => return self.command(),
+            '0'..='9' => {
+                let start = self.pos - 1;
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.bump();
+                }
+                let number = self.src[start..self.pos].to_string();
+                self.token("mn", &number)
+            }
+            '\'' => "<mo>′</mo>".to_string(),
+            '~' => "<mtext>&#160;</mtext>".to_string(),
+            c if c.is_alphabetic() => self.token("mi", c.encode_utf8(&mut [0; 4])),
+            c => format!("<mo>{}</mo>", encode_minimal(c.encode_utf8(&mut [0; 4]))),
+        };
+        Some(Atom::new(mathml))
+    }
+
+    fn close_group(&mut self) {
+        if self.peek() == Some('}') {
+            self.bump();
+        } else {
+            crate::diagnostics::warn("math: unclosed '{'");
+        }
+    }
+
+    /// An identifier or number, in the current font variant.
+    fn token(&self, tag: &str, text: &str) -> String {
+        match self.variant {
+            Some(variant) => format!(
+                r#"<{tag} mathvariant="{variant}">{}</{tag}>"#,
+                encode_minimal(text)
+            ),
+            None => format!("<{tag}>{}</{tag}>", encode_minimal(text)),
+        }
+    }
+
+    fn parse_scripts(&mut self, base: Atom) -> String {
+        let mut sub = None;
+        let mut sup = None;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('_') if sub.is_none() => {
+                    self.bump();
+                    sub = Some(self.argument("_"));
+                }
+                Some('^') if sup.is_none() => {
+                    self.bump();
+                    sup = Some(self.argument("^"));
+                }
+                _ => break,
+            }
+        }
+        let (under, over, both) = if base.limits {
+            ("munder", "mover", "munderover")
+        } else {
+            ("msub", "msup", "msubsup")
+        };
+        let base = base.mathml;
+        match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) => format!("<{under}>{base}{sub}</{under}>"),
+            (None, Some(sup)) => format!("<{over}>{base}{sup}</{over}>"),
+            (Some(sub), Some(sup)) => format!("<{both}>{base}{sub}{sup}</{both}>"),
+        }
+    }
+
+    /// A required argument: a group or a single token, as TeX takes it
+    /// (`\frac12` is ½).
+    fn argument(&mut self, of: &str) -> String {
+        self.skip_whitespace();
+        if let Some(digit) = self.peek().filter(char::is_ascii_digit) {
+            self.bump();
+            return self.token("mn", digit.encode_utf8(&mut [0; 4]));
+        }
+        if !self.at_row_end()
+            && self.peek() != Some('_')
+            && self.peek() != Some('^')
+            && let Some(atom) = self.parse_atom()
+        {
+            return atom.mathml;
+        }
+        crate::diagnostics::warn(format!("math: missing argument for '{of}'"));
+        "<mrow></mrow>".to_string()
+    }
+
+    /// The raw text of a `{…}` group, for arguments that aren't math.
+    fn raw_group(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        if self.peek() != Some('{') {
+            return None;
+        }
+        self.bump();
+        let start = self.pos;
+        let mut depth = 0;
+        while let Some(c) = self.bump() {
+            match c {
+                '\\' => {
+                    self.bump();
+                }
+                '{' => depth += 1,
+                '}' if depth == 0 => return Some(self.src[start..self.pos - 1].to_string()),
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        crate::diagnostics::warn("math: unclosed '{'");
+        Some(self.src[start..].to_string())
+    }
+
+    fn command(&mut self) -> Option<Atom> {
+        let name = self.command_name();
+        let mathml = match name.as_str() {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let numerator = self.argument(&format!(r"\{name}"));
+                let denominator = self.argument(&format!(r"\{name}"));
+                format!("<mfrac>{numerator}{denominator}</mfrac>")
+            }
+            "binom" => {
+                let top = self.argument(r"\binom");
+                let bottom = self.argument(r"\binom");
+                format!(
+                    r#"<mrow><mo>(</mo><mfrac linethickness="0">{top}{bottom}</mfrac><mo>)</mo></mrow>"#
+                )
+            }
+            "sqrt" => {
+                self.skip_whitespace();
+                let index = if self.peek() == Some('[') {
+                    self.bump();
+                    let start = self.pos;
+                    while self.peek().is_some_and(|c| c != ']') {
+                        self.bump();
+                    }
+                    let index = self.src[start..self.pos].to_string();
+                    self.bump();
+                    Some(index)
+                } else {
+                    None
+                };
+                let radicand = self.argument(r"\sqrt");
+                match index {
+                    Some(index) => {
+                        let index = Parser {
+                            src: &index,
+                            pos: 0,
+                            variant: None,
+                            depth: self.depth,
+                        }
+                        .parse_row();
+                        format!("<mroot>{radicand}{}</mroot>", row(index))
+                    }
+                    None => format!("<msqrt>{radicand}</msqrt>"),
+                }
+            }
+            "text" | "textrm" | "textit" | "textbf" | "mbox" => {
+                let text = self.raw_group().unwrap_or_default();
+                format!("<mtext>{}</mtext>", encode_minimal(&text))
+            }
+            "operatorname" => {
+                let text = self.raw_group().unwrap_or_default();
+                format!("<mi>{}</mi>", encode_minimal(&text))
+            }
+            "mathrm" | "mathbf" | "mathit" | "mathbb" | "mathcal" | "mathfrak" | "mathsf"
+            | "mathtt" | "boldsymbol" => {
+                let outer = self.variant.replace(match name.as_str() {
+                    "mathrm" => "normal",
+                    "mathbf" | "boldsymbol" => "bold",
+                    "mathit" => "italic",
+                    "mathbb" => "double-struck",
+                    "mathcal" => "script",
+                    "mathfrak" => "fraktur",
+                    "mathsf" => "sans-serif",
+                    _ => "monospace",
+                });
+                let argument = self.argument(&format!(r"\{name}"));
+                self.variant = outer;
+                argument
+            }
+            "left" => {
+                let open = self.delimiter();
+                let inner = self.parse_row();
+                let close = if self.at_command("right") {
+                    self.command_name_after_backslash();
+                    self.delimiter()
+                } else {
+                    crate::diagnostics::warn(r"math: '\left' without '\right'");
+                    String::new()
+                };
+                format!("<mrow>{open}{}{close}</mrow>", inner.concat())
+            }
+            "big" | "Big" | "bigg" | "Bigg" | "bigl" | "bigr" | "Bigl" | "Bigr" | "biggl"
+            | "biggr" | "Biggl" | "Biggr" => self.delimiter(),
+            "begin" => self.environment(),
+            "overline" | "bar" => self.accent("‾", &name),
+            "hat" | "widehat" => self.accent("^", &name),
+            "tilde" | "widetilde" => self.accent("~", &name),
+            "vec" => self.accent("→", &name),
+            "dot" => self.accent("˙", &name),
+            "ddot" => self.accent("¨", &name),
+            "underline" => {
+                let base = self.argument(r"\underline");
+                format!(r#"<munder accentunder="true">{base}<mo>_</mo></munder>"#)
+            }
+            "," => space("0.1667em"),
+            ":" | ">" => space("0.2222em"),
+            ";" => space("0.2778em"),
+            " " => space("0.25em"),
+            "quad" => space("1em"),
+            "qquad" => space("2em"),
+            "!" => space("-0.1667em"),
+            "displaystyle" | "textstyle" | "limits" | "nolimits" | "not" => return None,
+            "{" | "}" | "#" | "%" | "&" | "$" | "_" => {
+                format!("<mo>{}</mo>", encode_minimal(&name))
+            }
+            "|" => "<mo>‖</mo>".to_string(),
+            _ => match symbol(&name) {
+                Some(Symbol::Ident(text)) => self.token("mi", text),
+                Some(Symbol::Op(text)) => format!("<mo>{text}</mo>"),
+                Some(Symbol::LargeOp(text)) => {
+                    return Some(Atom {
+                        mathml: format!("<mo>{text}</mo>"),
+                        limits: true,
+                    });
+                }
+                Some(Symbol::Func(text, limits)) => {
+                    return Some(Atom {
+                        mathml: format!("<mi>{text}</mi>"),
+                        limits,
+                    });
+                }
+                None => {
+                    crate::diagnostics::warn(format!("math: unsupported command '\\{name}'"));
+                    format!(
+                        "<merror><mtext>\\{}</mtext></merror>",
+                        encode_minimal(&name)
+                    )
+                }
+            },
+        };
+        Some(Atom::new(mathml))
+    }
+
+    fn command_name_after_backslash(&mut self) {
+        self.bump();
+        self.command_name();
+    }
+
+    fn accent(&mut self, mark: &str, name: &str) -> String {
+        let base = self.argument(&format!(r"\{name}"));
+        format!(r#"<mover accent="true">{base}<mo>{mark}</mo></mover>"#)
+    }
+
+    /// The fence after `\left`, `\right` or `\big`; `.` is an invisible one.
+    fn delimiter(&mut self) -> String {
+        self.skip_whitespace();
+        let fence = match self.bump() {
+            Some('.') => return String::new(),
+            Some('\\') => match self.command_name().as_str() {
+                "{" | "lbrace" => "{",
+                "}" | "rbrace" => "}",
+                "|" | "Vert" | "lVert" | "rVert" => "‖",
+                "vert" | "lvert" | "rvert" => "|",
+                "langle" => "⟨",
+                "rangle" => "⟩",
+                "lfloor" => "⌊",
+                "rfloor" => "⌋",
+                "lceil" => "⌈",
+                "rceil" => "⌉",
+                other => {
+                    crate::diagnostics::warn(format!("math: unsupported delimiter '\\{other}'"));
+                    return String::new();
+                }
+            },
+            Some(c) if "()[]|/<>".contains(c) => {
+                return format!("<mo>{}</mo>", encode_minimal(c.encode_utf8(&mut [0; 4])));
+            }
+            _ => {
+                crate::diagnostics::warn("math: missing delimiter");
+                return String::new();
+            }
+        };
+        format!("<mo>{fence}</mo>")
+    }
+
+    /// `\begin{name} … \end{name}`: cells split on `&`, rows on `\\`.
+    fn environment(&mut self) -> String {
+        let name = self.raw_group().unwrap_or_default();
+        let (open, close, align) = match name.as_str() {
+            "matrix" | "smallmatrix" => ("", "", None),
+            "pmatrix" => ("(", ")", None),
+            "bmatrix" => ("[", "]", None),
+            "Bmatrix" => ("{", "}", None),
+            "vmatrix" => ("|", "|", None),
+            "Vmatrix" => ("‖", "‖", None),
+            "cases" => ("{", "", Some("left left")),
+            "aligned" | "align" | "align*" | "split" => ("", "", Some("right left")),
+            "gathered" | "gather" | "gather*" => ("", "", None),
+            "array" => {
+                // The column spec only sets alignment; cells stay centred.
+                self.raw_group();
+                ("", "", None)
+            }
+            _ => {
+                crate::diagnostics::warn(format!(
+                    "math: unsupported environment '{name}' — rendered as a matrix"
+                ));
+                ("", "", None)
+            }
+        };
+
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut cells = Vec::new();
+        loop {
+            cells.push(row(self.parse_row()));
+            if self.peek() == Some('&') {
+                self.bump();
+                continue;
+            }
+            if self.rest().starts_with(r"\\") {
+                self.pos += 2;
+                rows.push(std::mem::take(&mut cells));
+                continue;
+            }
+            rows.push(std::mem::take(&mut cells));
+            if self.at_command("end") {
+                self.command_name_after_backslash();
+                let end = self.raw_group().unwrap_or_default();
+                if end != name {
+                    crate::diagnostics::warn(format!(
+                        "math: '\\begin{{{name}}}' closed by '\\end{{{end}}}'"
+                    ));
+                }
+            } else {
+                crate::diagnostics::warn(format!("math: unclosed environment '{name}'"));
+            }
+            break;
+        }
+        // A trailing `\\` leaves an empty last row.
+        if rows.len() > 1
+            && rows
+                .last()
+                .is_some_and(|cells| cells.concat() == "<mrow></mrow>")
+        {
+            rows.pop();
+        }
+
+        let align = align.map_or(String::new(), |align| format!(r#" columnalign="{align}""#));
+        let mut table = format!("<mtable{align}>");
+        for cells in rows {
+            table.push_str("<mtr>");
+            for cell in cells {
+                table.push_str(&format!("<mtd>{cell}</mtd>"));
+            }
+            table.push_str("</mtr>");
+        }
+        table.push_str("</mtable>");
+        if open.is_empty() && close.is_empty() {
+            return table;
+        }
+        let fence = |f: &str| {
+            if f.is_empty() {
+                String::new()
+            } else {
+                format!("<mo>{f}</mo>")
+            }
+        };
+        format!("<mrow>{}{table}{}</mrow>", fence(open), fence(close))
+    }
+}
+
+/// One node as-is, several wrapped in `<mrow>`.
+fn row(nodes: Vec<String>) -> String {
+    if nodes.len() == 1 {
+        nodes.into_iter().next().unwrap_or_default()
+    } else {
+        format!("<mrow>{}</mrow>", nodes.concat())
+    }
+}
+
+fn space(width: &str) -> String {
+    format!(r#"<mspace width="{width}"></mspace>"#)
+}
+
+fn symbol(name: &str) -> Option<Symbol> {
+    use Symbol::*;
+    Some(match name {
+        // Greek
+        "alpha" => Ident("α"),
+        "beta" => Ident("β"),
+        "gamma" => Ident("γ"),
+        "delta" => Ident("δ"),
+        "epsilon" => Ident("ϵ"),
+        "varepsilon" => Ident("ε"),
+        "zeta" => Ident("ζ"),
+        "eta" => Ident("η"),
+        "theta" => Ident("θ"),
+        "vartheta" => Ident("ϑ"),
+        "iota" => Ident("ι"),
+        "kappa" => Ident("κ"),
+        "lambda" => Ident("λ"),
+        "mu" => Ident("μ"),
+        "nu" => Ident("ν"),
+        "xi" => Ident("ξ"),
+        "pi" => Ident("π"),
+        "varpi" => Ident("ϖ"),
+        "rho" => Ident("ρ"),
+        "varrho" => Ident("ϱ"),
+        "sigma" => Ident("σ"),
+        "varsigma" => Ident("ς"),
+        "tau" => Ident("τ"),
+        "upsilon" => Ident("υ"),
+        "phi" => Ident("ϕ"),
+        "varphi" => Ident("φ"),
+        "chi" => Ident("χ"),
+        "psi" => Ident("ψ"),
+        "omega" => Ident("ω"),
+        "Gamma" => Ident("Γ"),
+        "Delta" => Ident("Δ"),
+        "Theta" => Ident("Θ"),
+        "Lambda" => Ident("Λ"),
+        "Xi" => Ident("Ξ"),
+        "Pi" => Ident("Π"),
+        "Sigma" => Ident("Σ"),
+        "Upsilon" => Ident("Υ"),
+        "Phi" => Ident("Φ"),
+        "Psi" => Ident("Ψ"),
+        "Omega" => Ident("Ω"),
+        // Letter-like
+        "infty" => Ident("∞"),
+        "partial" => Ident("∂"),
+        "nabla" => Ident("∇"),
+        "emptyset" | "varnothing" => Ident("∅"),
+        "ell" => Ident("ℓ"),
+        "hbar" => Ident("ℏ"),
+        "Re" => Ident("ℜ"),
+        "Im" => Ident("ℑ"),
+        "aleph" => Ident("ℵ"),
+        // Binary operators and relations
+        "cdot" => Op("⋅"),
+        "times" => Op("×"),
+        "div" => Op("÷"),
+        "pm" => Op("±"),
+        "mp" => Op("∓"),
+        "ast" => Op("∗"),
+        "star" => Op("⋆"),
+        "circ" => Op("∘"),
+        "bullet" => Op("∙"),
+        "oplus" => Op("⊕"),
+        "otimes" => Op("⊗"),
+        "cup" => Op("∪"),
+        "cap" => Op("∩"),
+        "setminus" => Op("∖"),
+        "wedge" | "land" => Op("∧"),
+        "vee" | "lor" => Op("∨"),
+        "neg" | "lnot" => Op("¬"),
+        "leq" | "le" => Op("≤"),
+        "geq" | "ge" => Op("≥"),
+        "neq" | "ne" => Op("≠"),
+        "ll" => Op("≪"),
+        "gg" => Op("≫"),
+        "approx" => Op("≈"),
+        "equiv" => Op("≡"),
+        "cong" => Op("≅"),
+        "sim" => Op("∼"),
+        "simeq" => Op("≃"),
+        "propto" => Op("∝"),
+        "perp" => Op("⊥"),
+        "parallel" => Op("∥"),
+        "mid" => Op("∣"),
+        "in" => Op("∈"),
+        "notin" => Op("∉"),
+        "ni" => Op("∋"),
+        "subset" => Op("⊂"),
+        "subseteq" => Op("⊆"),
+        "supset" => Op("⊃"),
+        "supseteq" => Op("⊇"),
+        "forall" => Op("∀"),
+        "exists" => Op("∃"),
+        "to" | "rightarrow" => Op("→"),
+        "gets" | "leftarrow" => Op("←"),
+        "leftrightarrow" => Op("↔"),
+        "Rightarrow" | "implies" => Op("⇒"),
+        "Leftarrow" => Op("⇐"),
+        "Leftrightarrow" | "iff" => Op("⇔"),
+        "mapsto" => Op("↦"),
+        "uparrow" => Op("↑"),
+        "downarrow" => Op("↓"),
+        "ldots" | "dots" => Op("…"),
+        "cdots" => Op("⋯"),
+        "vdots" => Op("⋮"),
+        "ddots" => Op("⋱"),
+        "prime" => Op("′"),
+        "langle" => Op("⟨"),
+        "rangle" => Op("⟩"),
+        "lfloor" => Op("⌊"),
+        "rfloor" => Op("⌋"),
+        "lceil" => Op("⌈"),
+        "rceil" => Op("⌉"),
+        "vert" => Op("|"),
+        "Vert" => Op("‖"),
+        "lbrace" => Op("{"),
+        "rbrace" => Op("}"),
+        // Large operators
+        "sum" => LargeOp("∑"),
+        "prod" => LargeOp("∏"),
+        "coprod" => LargeOp("∐"),
+        "bigcup" => LargeOp("⋃"),
+        "bigcap" => LargeOp("⋂"),
+        "bigoplus" => LargeOp("⨁"),
+        "bigotimes" => LargeOp("⨂"),
+        // Integrals keep their scripts to the side.
+        "int" => Op("∫"),
+        "iint" => Op("∬"),
+        "iiint" => Op("∭"),
+        "oint" => Op("∮"),
+        // Function names
+        "lim" => Func("lim", true),
+        "max" => Func("max", true),
+        "min" => Func("min", true),
+        "sup" => Func("sup", true),
+        "inf" => Func("inf", true),
+        "limsup" => Func("lim sup", true),
+        "liminf" => Func("lim inf", true),
+        "det" => Func("det", true),
+        "gcd" => Func("gcd", true),
+        "Pr" => Func("Pr", true),
+        "sin" => Func("sin", false),
+        "cos" => Func("cos", false),
+        "tan" => Func("tan", false),
+        "sec" => Func("sec", false),
+        "csc" => Func("csc", false),
+        "cot" => Func("cot", false),
+        "arcsin" => Func("arcsin", false),
+        "arccos" => Func("arccos", false),
+        "arctan" => Func("arctan", false),
+        "sinh" => Func("sinh", false),
+        "cosh" => Func("cosh", false),
+        "tanh" => Func("tanh", false),
+        "coth" => Func("coth", false),
+        "log" => Func("log", false),
+        "ln" => Func("ln", false),
+        "lg" => Func("lg", false),
+        "exp" => Func("exp", false),
+        "dim" => Func("dim", false),
+        "ker" => Func("ker", false),
+        "deg" => Func("deg", false),
+        "hom" => Func("hom", false),
+        "arg" => Func("arg", false),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_common_constructs() {
+        assert_eq!(
+            to_mathml("x^2 + y_i", false),
+            "<math><mrow><msup><mi>x</mi><mn>2</mn></msup><mo>+</mo><msub><mi>y</mi><mi>i</mi></msub></mrow></math>"
+        );
+        assert_eq!(
+            to_mathml(r"\frac{a}{b}", true),
+            r#"<math display="block"><mfrac><mrow><mi>a</mi></mrow><mrow><mi>b</mi></mrow></mfrac></math>"#
+        );
+        assert_eq!(
+            to_mathml(r"\sum_{i=1}^n i", false),
+            "<math><mrow><munderover><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi></munderover><mi>i</mi></mrow></math>"
+        );
+        assert_eq!(
+            to_mathml(r"\sqrt[3]{x} \le \alpha", false),
+            "<math><mrow><mroot><mrow><mi>x</mi></mrow><mn>3</mn></mroot><mo>≤</mo><mi>α</mi></mrow></math>"
+        );
+        assert_eq!(
+            to_mathml(r"\mathbb{R}", false),
+            r#"<math><mrow><mi mathvariant="double-struck">R</mi></mrow></math>"#
+        );
+    }
+
+    #[test]
+    fn environments_build_tables() {
+        assert_eq!(
+            to_mathml(r"\begin{pmatrix} a & b \\ c & d \end{pmatrix}", true),
+            r#"<math display="block"><mrow><mo>(</mo><mtable><mtr><mtd><mi>a</mi></mtd><mtd><mi>b</mi></mtd></mtr><mtr><mtd><mi>c</mi></mtd><mtd><mi>d</mi></mtd></mtr></mtable><mo>)</mo></mrow></math>"#
+        );
+    }
+
+    #[test]
+    fn deep_nesting_is_cut_off() {
+        let tex = format!("{}x{}", "{".repeat(10_000), "}".repeat(10_000));
+        let (html, diagnostics) = crate::diagnostics::capture(|| to_mathml(&tex, true));
+        assert!(html.contains("<merror>"), "{html}");
+        assert_eq!(
+            diagnostics,
+            ["math: nested more than 128 levels deep — rest of the group skipped"]
+        );
+
+        let tex = r"\frac{".repeat(10_000);
+        let (html, _) = crate::diagnostics::capture(|| to_mathml(&tex, false));
+        assert!(html.contains("<merror>"), "{html}");
+    }
+
+    #[test]
+    fn unsupported_input_is_reported_not_dropped() {
+        let (html, diagnostics) =
+            crate::diagnostics::capture(|| to_mathml(r"\foo{x} + 1 }", false));
+        assert!(
+            html.contains(r"<merror><mtext>\foo</mtext></merror>"),
+            "{html}"
+        );
+        assert!(html.contains("<mn>1</mn>"), "{html}");
+        assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+    }
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(
+            to_mathml(r"a < b \text{<b>}", false),
+            "<math><mrow><mi>a</mi><mo>&lt;</mo><mi>b</mi><mtext>&lt;b&gt;</mtext></mrow></math>"
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use crate::types::ParseOptions;
+use std::cell::RefCell;
+
+thread_local! {
+    static OPTIONS: RefCell<Option<ParseOptions>> = const { RefCell::new(None) };
+}
+
+/// Makes `options` visible to every renderer on this thread for the duration of
+/// `run`. Scoped like `diagnostics::capture`, so link and verbatim rendering can
+/// read them without every signature between here and there carrying them.
+pub fn scoped<T>(options: ParseOptions, run: impl FnOnce() -> T) -> T {
+    let _restore = Restore(OPTIONS.with(|current| current.replace(Some(options))));
+    run()
+}
+
+/// Puts the previous options back when dropped, so a panicking `run` doesn't
+/// leave its options to later parses on the thread.
+struct Restore(Option<ParseOptions>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        OPTIONS.with(|current| {
+            current.replace(previous);
+        });
+    }
+}
+
+/// Reads the options in scope, or the defaults outside [`scoped`].
+pub fn with<R>(read: impl FnOnce(&ParseOptions) -> R) -> R {
+    OPTIONS.with(|current| match current.borrow().as_ref() {
+        Some(options) => read(options),
+        None => read(&ParseOptions::default()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_panicking_scope_restores_the_options() {
+        let options = ParseOptions {
+            id_prefix: Some("doc-".into()),
+            ..Default::default()
+        };
+        let result = std::panic::catch_unwind(|| scoped(options, || panic!("render failed")));
+        assert!(result.is_err());
+        assert_eq!(with(|options| options.id_prefix.clone()), None);
+    }
+}
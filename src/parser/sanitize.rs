@@ -0,0 +1,543 @@
+use crate::types::SanitizeOptions;
+use crate::utils::refused_url;
+
+/// Everything the renderer itself emits, so sanitizing a document without
+/// embedded HTML leaves it unchanged. `a-*` covers arborium's highlight
+/// elements.
+const DEFAULT_TAGS: &[&str] = &[
+    "a",
+    "a-*",
+    "abbr",
+    "aside",
+    "audio",
+    "b",
+    "blockquote",
+    "br",
+    "button",
+    "caption",
+    "code",
+    "dd",
+    "del",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "figcaption",
+    "figure",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "input",
+    "ins",
+    "kbd",
+    "li",
+    "mark",
+    "math",
+    "merror",
+    "mfrac",
+    "mi",
+    "mn",
+    "mo",
+    "mover",
+    "mroot",
+    "mrow",
+    "mspace",
+    "msqrt",
+    "msub",
+    "msubsup",
+    "msup",
+    "mtable",
+    "mtd",
+    "mtext",
+    "mtr",
+    "munder",
+    "munderover",
+    "ol",
+    "p",
+    "pre",
+    "s",
+    "section",
+    "small",
+    "span",
+    "strong",
+    "sub",
+    "summary",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "track",
+    "u",
+    "ul",
+    "var",
+    "video",
+];
+
+const DEFAULT_ATTRIBUTES: &[&str] = &[
+    "accent",
+    "accentunder",
+    "alt",
+    "aria-*",
+    "autoplay",
+    "checked",
+    "class",
+    "colspan",
+    "columnalign",
+    "controls",
+    "data-*",
+    "decoding",
+    "dir",
+    "disabled",
+    "display",
+    "height",
+    "hidden",
+    "href",
+    "id",
+    "kind",
+    "lang",
+    "linethickness",
+    "loading",
+    "loop",
+    "mathvariant",
+    "muted",
+    "open",
+    "poster",
+    "rel",
+    "role",
+    "rowspan",
+    "src",
+    "srclang",
+    "tabindex",
+    "target",
+    "title",
+    "type",
+    "width",
+];
+
+/// Elements whose content is raw text or never meant to render; dropping only
+/// the tags would spill their source into the page.
+const DROP_WITH_CONTENT: &[&str] = &[
+    "iframe",
+    "noembed",
+    "noframes",
+    "noscript",
+    "object",
+    "plaintext",
+    "script",
+    "style",
+    "template",
+    "textarea",
+    "title",
+    "xmp",
+];
+
+/// Attributes a browser resolves as URLs.
+const URL_ATTRIBUTES: &[&str] = &[
+    "action",
+    "background",
+    "cite",
+    "formaction",
+    "href",
+    "poster",
+    "src",
+    "xlink:href",
+];
+
+/// Rewrites `html` keeping only allowlisted elements and attributes, and
+/// records a diagnostic for every removal. A disallowed element loses its tags
+/// but keeps its content, except for [`DROP_WITH_CONTENT`] elements and
+/// comments, which go entirely.
+pub fn sanitize(html: &str, options: &SanitizeOptions) -> String {
+    let policy = Policy::new(options);
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        rest = policy.tag(&rest[start..], &mut out);
+    }
+    out.push_str(rest);
+    out
+}
+
+struct Policy {
+    tags: Vec<String>,
+    attributes: Vec<String>,
+}
+
+impl Policy {
+    fn new(options: &SanitizeOptions) -> Self {
+        let list = |configured: &Option<Vec<String>>, defaults: &[&str]| match configured {
+            Some(entries) => entries.iter().map(|e| e.to_ascii_lowercase()).collect(),
+            None => defaults.iter().map(|e| e.to_string()).collect(),
+        };
+        Self {
+            tags: list(&options.allowed_tags, DEFAULT_TAGS),
+            attributes: list(&options.allowed_attributes, DEFAULT_ATTRIBUTES),
+        }
+    }
+
+    /// Sanitizes the markup at the start of `html` (which begins with `<`) into
+    /// `out`, returning what follows it.
+    fn tag<'h>(&self, html: &'h str, out: &mut String) -> &'h str {
+        if let Some(comment) = html.strip_prefix("<!--") {
+            warn("removed an HTML comment");
+            return comment.find("-->").map_or("", |end| &comment[end + 3..]);
+        }
+
+        let (closing, body) = match html[1..].strip_prefix('/') {
+            Some(body) => (true, body),
+            None => (false, &html[1..]),
+        };
+        let name_len = body
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | ':')))
+            .unwrap_or(body.len());
+        if name_len == 0 || !body.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            // `< 3`, `<!doctype>`, `<?xml`: not an element, so it's text.
+            out.push_str("&lt;");
+            return &html[1..];
+        }
+
+        let name = body[..name_len].to_ascii_lowercase();
+        let Some((attributes, self_closing, rest)) = split_tag(&body[name_len..]) else {
+            // Never closed, so it can't be an element: the rest stays text.
+            warn(format!("escaped unterminated <{name}> tag"));
+            out.push_str("&lt;");
+            return &html[1..];
+        };
+
+        if closing {
+            if self.allows_tag(&name) {
+                out.push_str("</");
+                out.push_str(&name);
+                out.push('>');
+            }
+            return rest;
+        }
+        if DROP_WITH_CONTENT.contains(&name.as_str()) {
+            warn(format!("removed <{name}> element and its content"));
+            return skip_element(rest, &name);
+        }
+        if !self.allows_tag(&name) {
+            warn(format!("removed <{name}> tag"));
+            return rest;
+        }
+
+        let mut kept: Vec<(String, Option<String>)> = parse_attributes(attributes)
+            .into_iter()
+            .filter(|(attribute, value)| {
+                let reason = self.refuses_attribute(attribute, value.as_deref());
+                if let Some(reason) = reason {
+                    warn(format!("removed {reason} '{attribute}' from <{name}>"));
+                }
+                reason.is_none()
+            })
+            .collect();
+        if kept.iter().any(|(attribute, _)| attribute == "target") {
+            require_noopener(&mut kept);
+        }
+
+        out.push('<');
+        out.push_str(&name);
+        for (attribute, value) in kept {
+            out.push(' ');
+            out.push_str(&attribute);
+            if let Some(value) = value {
+                out.push_str("=\"");
+                out.push_str(&value.replace('"', "&quot;"));
+                out.push('"');
+            }
+        }
+        out.push_str(if self_closing { " />" } else { ">" });
+        rest
+    }
+
+    fn allows_tag(&self, name: &str) -> bool {
+        self.tags.iter().any(|entry| matches_entry(entry, name))
+    }
+
+    fn refuses_attribute(&self, name: &str, value: Option<&str>) -> Option<&'static str> {
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | ':'))
+        {
+            return Some("malformed attribute");
+        }
+        if name.starts_with("on") {
+            return Some("event handler");
+        }
+        if name == "style" {
+            return Some("style attribute");
+        }
+        if !self
+            .attributes
+            .iter()
+            .any(|entry| matches_entry(entry, name))
+        {
+            return Some("attribute");
+        }
+        // Browsers decode entities before resolving, so `&#106;avascript:` is
+        // checked as `javascript:`.
+        let url = value.filter(|_| URL_ATTRIBUTES.contains(&name))?;
+        refused_url(&decode_entities(url)).map(|_| "unsafe URL in")
+    }
+}
+
+fn warn(message: impl std::fmt::Display) {
+    crate::diagnostics::warn(format!("sanitize: {message}"));
+}
+
+fn matches_entry(entry: &str, name: &str) -> bool {
+    match entry.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => entry == name,
+    }
+}
+
+/// Splits a tag after its name into the attribute text, whether it
+/// self-closes, and what follows the `>`. `None` if the `>` never comes.
+fn split_tag(s: &str) -> Option<(&str, bool, &str)> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => {
+                let attributes = s[..i].trim_end();
+                return Some(match attributes.strip_suffix('/') {
+                    Some(attributes) => (attributes, true, &s[i + 1..]),
+                    None => (attributes, false, &s[i + 1..]),
+                });
+            }
+            (None, _) => {}
+        }
+    }
+    None
+}
+
+/// A page opened with `target` can reach back through `window.opener` unless
+/// the link says `noopener`.
+fn require_noopener(attributes: &mut Vec<(String, Option<String>)>) {
+    match attributes
+        .iter_mut()
+        .find(|(attribute, _)| attribute == "rel")
+    {
+        Some((_, value)) => {
+            let rel = value.get_or_insert_default();
+            if !rel
+                .split_ascii_whitespace()
+                .any(|token| token.eq_ignore_ascii_case("noopener"))
+            {
+                if !rel.is_empty() {
+                    rel.push(' ');
+                }
+                rel.push_str("noopener");
+            }
+        }
+        None => attributes.push(("rel".to_string(), Some("noopener".to_string()))),
+    }
+}
+
+/// Attribute names lowercased, values still entity-encoded as written. A name
+/// ends where the HTML tokenizer ends it — at whitespace, `/`, `=` or `>` — so
+/// `data-x/onload=…` is two attributes here, as it is in a browser.
+fn parse_attributes(mut s: &str) -> Vec<(String, Option<String>)> {
+    let mut attributes = Vec::new();
+    loop {
+        s = s.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if s.is_empty() {
+            return attributes;
+        }
+        // A leading `=` belongs to the name, as in the tokenizer.
+        let name_len = s
+            .char_indices()
+            .skip(1)
+            .find(|&(_, c)| c.is_whitespace() || matches!(c, '/' | '=' | '>'))
+            .map_or(s.len(), |(i, _)| i);
+        let name = s[..name_len].to_ascii_lowercase();
+        s = s[name_len..].trim_start();
+
+        let Some(after_eq) = s.strip_prefix('=') else {
+            attributes.push((name, None));
+            continue;
+        };
+        let after_eq = after_eq.trim_start();
+        let (value, rest) = match after_eq.chars().next() {
+            Some(q @ ('"' | '\'')) => {
+                let inner = &after_eq[1..];
+                let end = inner.find(q).unwrap_or(inner.len());
+                (&inner[..end], inner.get(end + 1..).unwrap_or(""))
+            }
+            _ => {
+                let end = after_eq.find(char::is_whitespace).unwrap_or(after_eq.len());
+                (&after_eq[..end], &after_eq[end..])
+            }
+        };
+        attributes.push((name, Some(value.to_string())));
+        s = rest;
+    }
+}
+
+/// Skips past the `</name>` closing an element dropped with its content.
+fn skip_element<'h>(html: &'h str, name: &str) -> &'h str {
+    // ASCII lowercasing keeps byte offsets, so indices carry over to `html`.
+    let lower = html.to_ascii_lowercase();
+    lower
+        .find(&format!("</{name}"))
+        .and_then(|start| html[start..].find('>').map(|end| &html[start + end + 1..]))
+        .unwrap_or("")
+}
+
+fn decode_entities(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        let (decoded, len) = decode_entity(&rest[amp + 1..]).unwrap_or(('&', 0));
+        out.push(decoded);
+        rest = &rest[amp + 1 + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Decodes the entity after an `&`, returning it and how many bytes it spans.
+/// Numeric references decode without a closing `;`, as browsers do; named ones
+/// are limited to those that can hide a URL scheme.
+fn decode_entity(s: &str) -> Option<(char, usize)> {
+    if s.starts_with('#') {
+        let (radix, digits_at) = if s[1..].starts_with(['x', 'X']) {
+            (16, 2)
+        } else {
+            (10, 1)
+        };
+        let digits = &s[digits_at..];
+        let len = digits
+            .find(|c: char| !c.is_digit(radix))
+            .unwrap_or(digits.len());
+        let code = u32::from_str_radix(&digits[..len], radix).ok()?;
+        let semicolon = usize::from(digits[len..].starts_with(';'));
+        return Some((
+            char::from_u32(code).unwrap_or('\u{FFFD}'),
+            digits_at + len + semicolon,
+        ));
+    }
+    let end = s.find(';')?;
+    let decoded = match &s[..end] {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "colon" => ':',
+        "Tab" => '\t',
+        "NewLine" => '\n',
+        _ => return None,
+    };
+    Some((decoded, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(html: &str) -> (String, Vec<String>) {
+        crate::diagnostics::capture(|| sanitize(html, &SanitizeOptions::default()))
+    }
+
+    #[test]
+    fn rendered_markup_passes_through_unchanged() {
+        let html = concat!(
+            r#"<h1 id="intro">Intro</h1>"#,
+            r#"<p><a href="https://example.com" target="_blank" rel="noopener noreferrer">x</a></p>"#,
+            r#"<pre class="arborium lang-rust"><code><span class="line"><a-k>fn</a-k></span></code></pre>"#,
+            r#"<input type="checkbox" class="todo-status todo-done" checked disabled />"#,
+        );
+        let (out, diagnostics) = clean(html);
+        assert_eq!(out, html);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+    }
+
+    #[test]
+    fn scripts_handlers_and_styles_are_removed_and_reported() {
+        let (out, diagnostics) = clean(
+            r#"<div onclick="steal()" style="color:red" class="ok"><script>alert(1)</SCRIPT>text<blink>b</blink></div>"#,
+        );
+        assert_eq!(out, r#"<div class="ok">textb</div>"#);
+        assert_eq!(diagnostics.len(), 4, "{diagnostics:?}");
+        assert!(diagnostics[0].contains("event handler 'onclick'"));
+        assert!(diagnostics[1].contains("style attribute 'style'"));
+        assert!(diagnostics[2].contains("<script> element"));
+        assert!(diagnostics[3].contains("<blink> tag"));
+    }
+
+    #[test]
+    fn entity_encoded_script_urls_are_caught() {
+        let (out, diagnostics) = clean(r#"<a href="&#106avascript&colon;alert(1)">x</a>"#);
+        assert_eq!(out, "<a>x</a>");
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    }
+
+    #[test]
+    fn configured_allowlists_replace_the_defaults() {
+        let options = SanitizeOptions {
+            allowed_tags: Some(vec!["p".into()]),
+            allowed_attributes: Some(vec!["data-*".into()]),
+        };
+        let (out, _) = crate::diagnostics::capture(|| {
+            sanitize(r#"<p id="x" data-track="hero"><em>hi</em></p>"#, &options)
+        });
+        assert_eq!(out, r#"<p data-track="hero">hi</p>"#);
+    }
+
+    #[test]
+    fn slashes_separate_attributes() {
+        let (out, diagnostics) = clean(r#"<img data-x/onload=alert(1) src="a.png">"#);
+        assert_eq!(out, r#"<img data-x src="a.png">"#);
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert!(diagnostics[0].contains("event handler 'onload'"));
+
+        let (out, _) = clean(r#"<img src="a.png" "onerror"=x>"#);
+        assert_eq!(out, r#"<img src="a.png">"#);
+
+        // A tag running into the next keeps its `<p` as a (malformed) name.
+        let (out, diagnostics) = clean("<img src=x onerror=alert(1) <p>after");
+        assert_eq!(out, r#"<img src="x">after"#);
+        assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+    }
+
+    #[test]
+    fn targets_always_get_noopener() {
+        let (out, _) =
+            clean(r#"<a href="/x" target="_blank">x</a><a target="_top" rel="nofollow">y</a>"#);
+        assert_eq!(
+            out,
+            r#"<a href="/x" target="_blank" rel="noopener">x</a><a target="_top" rel="nofollow noopener">y</a>"#
+        );
+    }
+
+    #[test]
+    fn unterminated_tags_stay_text() {
+        let (out, diagnostics) = clean("<p>a</p>after</p");
+        assert_eq!(out, "<p>a</p>after&lt;/p");
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    }
+
+    #[test]
+    fn stray_brackets_are_escaped_as_text() {
+        let (out, diagnostics) = clean("a < b <!doctype html>");
+        assert_eq!(out, "a &lt; b &lt;!doctype html>");
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+    }
+}
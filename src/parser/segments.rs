@@ -1,4 +1,5 @@
-use crate::utils::{UrlKind, has_unsafe_scheme, into_slug};
+use crate::types::{AssetKind, HeadingOverflow, SlugStyle};
+use crate::utils::{UrlKind, is_plain_id, refused_url, slugify};
 use htmlescape::encode_minimal;
 use rust_norg::{LinkTarget, ParagraphSegment, ParagraphSegmentToken};
 use std::collections::{HashMap, HashSet};
@@ -149,24 +150,66 @@ fn push_escaped(out: &mut String, s: &str) {
     }
 }
 
-/// Renders a heading's final HTML, its slug id, and its level clamped to the
-/// `<h1>`–`<h6>` range, so the renderer and the TOC can't derive any of the
-/// three differently for the same heading (`rust_norg` parses 7+ `*` as level
-/// 7+, but HTML has no `<h7>`). `seen` de-duplicates ids across the document so
-/// two headings with the same title don't emit the same (invalid) id.
-pub fn heading_html_and_id(
+/// A heading as both the renderer and the TOC see it.
+pub struct HeadingParts {
+    /// The title's final HTML, without the number.
+    pub html: String,
+    pub id: String,
+    /// Output level, after `heading_offset` and the overflow policy.
+    pub level: u16,
+    /// Hierarchical section number (`1.2.3`), when numbering is on.
+    pub number: Option<String>,
+}
+
+/// Renders a heading's title HTML, id, number and output level — offset by
+/// `heading_offset`, and past 6 left for an ARIA heading unless the overflow
+/// policy clamps it — so the renderer and the TOC can't derive any of them
+/// differently for the same heading (`rust_norg` parses 7+ `*` as level 7+,
+/// but HTML has no `<h7>`). Ids and numbers come from the [`DocumentIds`]
+/// pre-pass, which de-duplicates ids across the document.
+pub fn heading_parts(
     title: &[ParagraphSegment],
     level: u16,
     ids: &mut DocumentIds,
-) -> (String, String, u16) {
-    let id = ids.next_heading();
+) -> HeadingParts {
+    let (id, number) = ids.next_heading();
     let html = convert_segments_with_ids(title, ids);
-    (html, id, level.min(6))
+    HeadingParts {
+        html,
+        id,
+        level: output_level(level),
+        number,
+    }
+}
+
+/// The level a heading at source `level` renders at, after `headingOffset`
+/// and the `headingOverflow` policy.
+pub fn output_level(level: u16) -> u16 {
+    crate::options::with(|options| {
+        let offset = u16::try_from(options.heading_offset.unwrap_or(0)).unwrap_or(u16::MAX);
+        let level = level.saturating_add(offset);
+        match options.heading_overflow.unwrap_or(HeadingOverflow::aria) {
+            HeadingOverflow::clamp => level.min(6),
+            HeadingOverflow::aria => level,
+        }
+    })
+}
+
+/// One visible heading, as the id pre-pass found it.
+pub struct HeadingSlot {
+    pub level: u16,
+    /// Normalised title that `{* Title}` links are matched against.
+    pub key: String,
+    pub slug: String,
+    /// Set by a `+id` carryover; emitted as written instead of the slug.
+    pub custom_id: Option<String>,
 }
 
 #[derive(Default)]
 pub struct DocumentIds {
     headings: Vec<String>,
+    /// Parallel to `headings`; all `None` unless numbering is on.
+    numbers: Vec<Option<String>>,
     footnotes: Vec<String>,
     heading_links: HashMap<(u16, String), String>,
     footnote_links: HashMap<String, String>,
@@ -175,33 +218,74 @@ pub struct DocumentIds {
 }
 
 impl DocumentIds {
-    pub fn new(headings: Vec<(u16, String, String)>, footnotes: Vec<(String, String)>) -> Self {
+    /// `custom_ids` holds every `+id` in the document, headings' included.
+    pub fn new(
+        headings: Vec<HeadingSlot>,
+        footnotes: Vec<(String, String)>,
+        custom_ids: &[String],
+    ) -> Self {
+        // Author-chosen ids are used as written, so they're claimed before any
+        // generated id can land on them.
         let mut allocator = IdAllocator::default();
-        for (_, _, slug) in &headings {
-            allocator.reserve(slug);
+        for id in custom_ids {
+            if !allocator.claim(id) {
+                crate::diagnostics::warn(format!(
+                    "duplicate custom id '{id}' — ids must be unique in a document"
+                ));
+            }
         }
-        for (_, slug) in &footnotes {
-            allocator.reserve(&format!("footnote-{slug}"));
+
+        // Prefixed before allocation, so de-duplication runs in the namespace
+        // the ids are actually emitted in.
+        let headings: Vec<_> = headings
+            .into_iter()
+            .map(|slot| {
+                let base = heading_id(&slot.slug);
+                (slot, base)
+            })
+            .collect();
+        let footnotes: Vec<_> = footnotes
+            .into_iter()
+            .map(|(key, slug)| (key, footnote_id(&slug)))
+            .collect();
+
+        for (slot, base) in &headings {
+            if slot.custom_id.is_none() {
+                allocator.reserve(base);
+            }
+        }
+        for (_, base) in &footnotes {
+            allocator.reserve(base);
         }
 
-        let mut ids = Self::default();
-        for (level, key, slug) in headings {
-            let id = allocator.allocate(slug.clone());
+        let mut ids = Self {
+            numbers: heading_numbers(&headings),
+            ..Self::default()
+        };
+        for (slot, base) in headings {
+            let id = match slot.custom_id {
+                Some(id) => id,
+                None => allocator.allocate(base),
+            };
             ids.heading_links
-                .entry((level, key))
+                .entry((slot.level, slot.key))
                 .or_insert_with(|| id.clone());
             ids.headings.push(id);
         }
-        for (key, slug) in footnotes {
-            let id = allocator.allocate(format!("footnote-{slug}"));
+        for (key, base) in footnotes {
+            let id = allocator.allocate(base);
             ids.footnote_links.entry(key).or_insert_with(|| id.clone());
             ids.footnotes.push(id);
         }
         ids
     }
 
-    fn next_heading(&mut self) -> String {
-        take_id(&self.headings, &mut self.next_heading, "heading")
+    fn next_heading(&mut self) -> (String, Option<String>) {
+        let number = self.numbers.get(self.next_heading).cloned().flatten();
+        (
+            take_id(&self.headings, &mut self.next_heading, "heading"),
+            number,
+        )
     }
 
     pub fn next_footnote(&mut self) -> String {
@@ -229,6 +313,75 @@ impl DocumentIds {
     }
 }
 
+/// Hierarchical numbers for the visible headings, when numbering is on. Depth
+/// follows the nesting actually present, so a `***` directly under a `*` is
+/// numbered `1.1`, not `1.0.1`. A shallower heading that closes such a scope
+/// carries on from its count, so `*`, `***`, `**` gives `1`, `1.1`, `1.2`.
+fn heading_numbers(headings: &[(HeadingSlot, String)]) -> Vec<Option<String>> {
+    if !crate::options::with(|options| options.numbering.unwrap_or(false)) {
+        return vec![None; headings.len()];
+    }
+    // (level, count) for each open heading scope, outermost first.
+    let mut counters: Vec<(u16, u32)> = Vec::new();
+    headings
+        .iter()
+        .map(|(slot, _)| {
+            // The shallowest scope closed here, which sat at this depth.
+            let mut closed = None;
+            while counters
+                .last()
+                .is_some_and(|&(level, _)| level > slot.level)
+            {
+                closed = counters.pop();
+            }
+            match counters.last_mut() {
+                Some((level, count)) if *level == slot.level => *count += 1,
+                _ => counters.push((slot.level, closed.map_or(1, |(_, count)| count + 1))),
+            }
+            let parts: Vec<_> = counters
+                .iter()
+                .map(|(_, count)| count.to_string())
+                .collect();
+            Some(parts.join("."))
+        })
+        .collect()
+}
+
+/// The `id_prefix` option, or nothing if it holds characters ids can't carry
+/// unescaped; [`warn_invalid_id_prefix`] reports that.
+pub fn id_prefix() -> String {
+    crate::options::with(|options| {
+        options
+            .id_prefix
+            .clone()
+            .filter(|prefix| is_plain_id(prefix))
+            .unwrap_or_default()
+    })
+}
+
+pub fn warn_invalid_id_prefix() {
+    if let Some(prefix) = crate::options::with(|options| options.id_prefix.clone())
+        && !is_plain_id(&prefix)
+    {
+        crate::diagnostics::warn(format!(
+            "idPrefix '{prefix}' may only contain letters, digits, '-' and '_' — ignored"
+        ));
+    }
+}
+
+/// The base id for a heading slug: prefixed, unless the slug is empty and the
+/// heading gets no id at all.
+fn heading_id(slug: &str) -> String {
+    if slug.is_empty() {
+        return String::new();
+    }
+    format!("{}{slug}", id_prefix())
+}
+
+fn footnote_id(slug: &str) -> String {
+    format!("{}footnote-{slug}", id_prefix())
+}
+
 /// Release-mode net only — [`DocumentIds::unconsumed`] is what actually catches
 /// a desync. An anchorless heading beats panicking out of the parse thread.
 fn take_id(ids: &[String], next: &mut usize, kind: &str) -> String {
@@ -250,6 +403,11 @@ struct IdAllocator {
 }
 
 impl IdAllocator {
+    /// Takes `id` as-is, returning `false` if it was already handed out.
+    fn claim(&mut self, id: &str) -> bool {
+        self.seen.insert(id.to_string())
+    }
+
     fn reserve(&mut self, id: &str) {
         if !id.is_empty() {
             self.reserved.insert(id.to_string());
@@ -275,11 +433,12 @@ impl IdAllocator {
 /// *text* rather than its rendered HTML — so a title like `{url}[Install]` slugs
 /// to `install`, not the `<a href…>` markup `convert_segments` would emit. Every
 /// base-slug site routes through here so links and generated IDs use the same
-/// visible-text rules.
+/// visible-text rules, and the configured [`SlugStyle`].
 pub fn title_slug(title: &[ParagraphSegment]) -> String {
     let mut text = String::new();
     push_title_text(title, &mut text);
-    into_slug(&text)
+    let style = crate::options::with(|options| options.slug_style.unwrap_or(SlugStyle::standard));
+    slugify(&text, style)
 }
 
 pub fn title_key(title: &[ParagraphSegment]) -> String {
@@ -479,6 +638,32 @@ fn push_title_text(segments: &[ParagraphSegment], out: &mut String) {
     }
 }
 
+/// Rebuilds the source text of `segments`, for content that is verbatim to
+/// Norg but was still tokenised: TeX backslashes come back from escapes, and
+/// `_`/`^` pairs the parser took for modifiers get their markers back.
+fn push_source_text(segments: &[ParagraphSegment], out: &mut String) {
+    for segment in segments {
+        match segment {
+            ParagraphSegment::Token(ParagraphSegmentToken::Whitespace) => out.push(' '),
+            ParagraphSegment::Token(ParagraphSegmentToken::Text(text)) => out.push_str(text),
+            ParagraphSegment::Token(ParagraphSegmentToken::Special(c)) => out.push(*c),
+            ParagraphSegment::Token(ParagraphSegmentToken::Escape(c)) => {
+                out.push('\\');
+                out.push(*c);
+            }
+            ParagraphSegment::AttachedModifier {
+                modifier_type,
+                content,
+            } => {
+                out.push(*modifier_type);
+                push_source_text(content, out);
+                out.push(*modifier_type);
+            }
+            other => push_title_text(std::slice::from_ref(other), out),
+        }
+    }
+}
+
 fn convert_attached_modifier(
     modifier_type: char,
     content: &[ParagraphSegment],
@@ -492,6 +677,12 @@ fn convert_attached_modifier(
         out.push_str("</code>");
         return;
     }
+    if modifier_type == '$' {
+        let mut tex = String::new();
+        push_source_text(content, &mut tex);
+        out.push_str(&crate::math::to_mathml(&tex, false));
+        return;
+    }
     let (open, close) = match modifier_type {
         '*' => ("<strong>", "</strong>"),
         '_' => ("<em>", "</em>"),
@@ -499,7 +690,6 @@ fn convert_attached_modifier(
         ',' => ("<sub>", "</sub>"),
         '-' => ("<s>", "</s>"),
         '!' => (r#"<span class="spoiler">"#, "</span>"),
-        '$' => (r#"<span class="math">"#, "</span>"),
         '&' => ("<var>", "</var>"),
         '/' => ("<i>", "</i>"),
         '=' => ("<mark>", "</mark>"),
@@ -527,15 +717,15 @@ fn norg_to_html(path: &str) -> String {
 /// would double-encode descriptions and render their inline markup as text.
 ///
 /// Two safety measures apply here, the single chokepoint for every link:
-/// a target with an unsafe URL scheme (`javascript:`, scriptable `data:`, …) is
-/// dropped to its plain display text rather than emitted as a clickable script
-/// URL, and external links get `rel="noopener noreferrer"` alongside
+/// a target with an unsafe URL scheme (`javascript:`, scriptable `data:`, …), or
+/// one outside a configured scheme allowlist, is dropped to its plain display
+/// text rather than emitted as a clickable URL, and external links get `rel="noopener noreferrer"` alongside
 /// `target="_blank"` to prevent the opened page from hijacking `window.opener`.
 /// When `nested`, this link sits inside another link's display, so only the
 /// display text is emitted (an `<a>` inside an `<a>` is invalid HTML).
 fn anchor(out: &mut String, href: &str, display_html: &str, external: bool, nested: bool) {
-    if has_unsafe_scheme(href) {
-        crate::diagnostics::warn(format!("dropping link with unsafe URL scheme: {href}"));
+    if let Some(reason) = refused_url(href) {
+        crate::diagnostics::warn(format!("dropping link with {reason}: {href}"));
         out.push_str(display_html);
         return;
     }
@@ -543,6 +733,7 @@ fn anchor(out: &mut String, href: &str, display_html: &str, external: bool, nest
         out.push_str(display_html);
         return;
     }
+    let href = crate::assets::reference(href, AssetKind::link);
     let target = if external {
         r#" target="_blank" rel="noopener noreferrer""#
     } else {
@@ -551,7 +742,7 @@ fn anchor(out: &mut String, href: &str, display_html: &str, external: bool, nest
     let _ = write!(
         out,
         r#"<a href="{}"{target}>{display_html}</a>"#,
-        encode_minimal(href)
+        encode_minimal(&href)
     );
 }
 
@@ -587,11 +778,10 @@ fn convert_link(
             // target; keep the path instead of degrading to a same-page anchor.
             let href = match filepath {
                 Some(fp) => format!("{}#{slug}", norg_to_html(fp)),
-                None => format!(
-                    "#{}",
-                    ids.and_then(|ids| ids.heading_link(*level, &key))
-                        .unwrap_or(&slug)
-                ),
+                None => match ids.and_then(|ids| ids.heading_link(*level, &key)) {
+                    Some(id) => format!("#{id}"),
+                    None => format!("#{}", heading_id(&slug)),
+                },
             };
             // Only render the title HTML when there's no description to use.
             let display_html = display.unwrap_or_else(|| render_segments(title, true, ids));
@@ -609,7 +799,7 @@ fn convert_link(
                 None => ids
                     .and_then(|ids| ids.footnote_link(&key))
                     .map(|id| format!("#{id}"))
-                    .unwrap_or_else(|| format!("#footnote-{slug}")),
+                    .unwrap_or_else(|| format!("#{}", footnote_id(&slug))),
             };
             let display_html = display.unwrap_or_else(|| render_segments(title, true, ids));
             Some((href, display_html, false))
@@ -0,0 +1,333 @@
+use crate::ast_handlers::{VerbatimTag, comment_scope, flat_comment_end, is_tangle_tag};
+use crate::metadata::extract_metadata;
+use rust_norg::{NorgAST, NorgASTFlat};
+use serde_json::Value;
+use std::collections::HashMap;
+use textwrap::dedent;
+
+/// Where `@code` blocks without a target of their own go, from the `tangle:`
+/// key of the document's metadata: a path for every block, or a `languages:`
+/// mapping from a block's language to a path.
+#[derive(Default)]
+struct DocumentTargets {
+    all: Option<String>,
+    languages: HashMap<String, String>,
+}
+
+impl DocumentTargets {
+    fn new(ast: &[NorgAST]) -> Self {
+        let mut targets = Self::default();
+        match extract_metadata(ast).remove("tangle") {
+            None => {}
+            Some(Value::String(path)) => targets.all = Some(path),
+            Some(Value::Object(mut tangle)) => match tangle.remove("languages") {
+                Some(Value::Object(languages)) => {
+                    for (language, path) in languages {
+                        match path {
+                            Value::String(path) => {
+                                targets.languages.insert(language, path);
+                            }
+                            _ => crate::diagnostics::warn(format!(
+                                "tangle target for language '{language}' is not a path — ignored"
+                            )),
+                        }
+                    }
+                }
+                _ => crate::diagnostics::warn(
+                    "tangle: in @document.meta has no languages: mapping — ignored",
+                ),
+            },
+            Some(_) => crate::diagnostics::warn(
+                "tangle: in @document.meta is neither a path nor a mapping — ignored",
+            ),
+        }
+        targets
+    }
+
+    fn get(&self, language: &str) -> Option<&str> {
+        self.languages
+            .get(language)
+            .or(self.all.as_ref())
+            .map(String::as_str)
+    }
+}
+
+/// A visible `@code` block and the `#tangle` carryovers on it.
+struct CodeBlock<'a> {
+    language: &'a str,
+    content: &'a str,
+    /// The file an empty block includes (`file=`), which tangling can't read.
+    file: Option<&'a str>,
+    /// The path each `#tangle` names; `None` for a bare `#tangle`.
+    targets: Vec<Option<&'a str>>,
+    /// Set by `#tangle.none`, which keeps the block out of every file.
+    excluded: bool,
+}
+
+/// Gathers the document's `@code` blocks into files: each block goes to the
+/// path of its `#tangle` carryover, or else to the one the `tangle:` metadata
+/// gives its language, and each file is its blocks in document order,
+/// separated by blank lines. Commented-out blocks are left out. Blocks with
+/// conflicting targets, `#tangle` blocks with none, blocks whose target leaves
+/// the document's directory and blocks that include a file are reported and
+/// skipped.
+pub fn tangle(ast: &[NorgAST]) -> HashMap<String, String> {
+    let document = DocumentTargets::new(ast);
+    let mut blocks = Vec::new();
+    collect_blocks(ast, &mut blocks);
+
+    let mut files: HashMap<String, Vec<String>> = HashMap::new();
+    for (index, block) in blocks.iter().enumerate() {
+        if block.excluded {
+            continue;
+        }
+        let mut paths: Vec<&str> = block.targets.iter().flatten().copied().collect();
+        paths.sort_unstable();
+        paths.dedup();
+        let target = match paths[..] {
+            [] if block.targets.is_empty() => document.get(block.language),
+            [] => {
+                let target = document.get(block.language);
+                if target.is_none() {
+                    crate::diagnostics::warn(format!(
+                        "code block #{} ({}) is marked #tangle but has no target — give it a \
+                         path, or map its language under tangle: in @document.meta",
+                        index + 1,
+                        block.language
+                    ));
+                }
+                target
+            }
+            [path] => Some(path),
+            _ => {
+                crate::diagnostics::warn(format!(
+                    "code block #{} ({}) has conflicting tangle targets {} — skipped",
+                    index + 1,
+                    block.language,
+                    paths.join(", ")
+                ));
+                None
+            }
+        };
+        let Some(target) = target else {
+            continue;
+        };
+        if !stays_inside(target) {
+            crate::diagnostics::warn(format!(
+                "code block #{} ({}) targets {target}, outside the document's directory — skipped",
+                index + 1,
+                block.language
+            ));
+            continue;
+        }
+        if let Some(file) = block.file
+            && block.content.trim().is_empty()
+        {
+            crate::diagnostics::warn(format!(
+                "code block #{} ({}) includes {file}, which tangling doesn't read — skipped",
+                index + 1,
+                block.language
+            ));
+            continue;
+        }
+        let code = dedent(block.content);
+        files
+            .entry(target.to_string())
+            .or_default()
+            .push(code.trim_end_matches('\n').to_string());
+    }
+    files
+        .into_iter()
+        .map(|(path, blocks)| (path, blocks.join("\n\n") + "\n"))
+        .collect()
+}
+
+/// Whether `path` stays within the document's directory: relative, and never
+/// climbing above where it starts.
+fn stays_inside(path: &str) -> bool {
+    if path.starts_with(['/', '\\']) || path.contains(':') {
+        return false;
+    }
+    let mut depth = 0usize;
+    for segment in path.split(['/', '\\']) {
+        match segment {
+            "" | "." => {}
+            ".." => match depth.checked_sub(1) {
+                Some(up) => depth = up,
+                None => return false,
+            },
+            _ => depth += 1,
+        }
+    }
+    true
+}
+
+fn collect_blocks<'a>(nodes: &'a [NorgAST], out: &mut Vec<CodeBlock<'a>>) {
+    let mut index = 0;
+    while index < nodes.len() {
+        if let Some(scope) = comment_scope(nodes, index) {
+            for node in scope.visible {
+                collect_blocks(std::slice::from_ref(node), out);
+            }
+            index = scope.end;
+            continue;
+        }
+        collect_node(&nodes[index], CarryoverTargets::default(), out);
+        index += 1;
+    }
+}
+
+/// The `#tangle` carryovers seen on the way to a node.
+#[derive(Default)]
+struct CarryoverTargets<'a> {
+    targets: Vec<Option<&'a str>>,
+    excluded: bool,
+}
+
+impl<'a> CarryoverTargets<'a> {
+    fn add(&mut self, name: &[String], parameters: &'a [String]) {
+        match name {
+            [_, none] if none == "none" => self.excluded = true,
+            [_] => self.targets.push(parameters.first().map(String::as_str)),
+            _ => crate::diagnostics::warn(format!(
+                "unknown tangle carryover '{}' — ignored",
+                name.join(".")
+            )),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.targets.is_empty() && !self.excluded
+    }
+
+    /// A code block carrying these targets, or, for anything else, a report
+    /// that they have nothing to apply to.
+    fn apply(
+        self,
+        name: &[String],
+        parameters: &'a [String],
+        content: &'a str,
+        out: &mut Vec<CodeBlock<'a>>,
+    ) {
+        if !matches!(VerbatimTag::from(name), VerbatimTag::Code) {
+            self.warn_ignored();
+            return;
+        }
+        out.push(CodeBlock {
+            language: parameters
+                .first()
+                .filter(|language| !language.is_empty())
+                .map_or("text", String::as_str),
+            content,
+            file: parameters
+                .iter()
+                .find_map(|parameter| parameter.strip_prefix("file=")),
+            targets: self.targets,
+            excluded: self.excluded,
+        });
+    }
+
+    fn warn_ignored(&self) {
+        if !self.is_empty() {
+            crate::diagnostics::warn("#tangle applies only to @code blocks — ignored");
+        }
+    }
+}
+
+fn collect_node<'a>(
+    node: &'a NorgAST,
+    mut carried: CarryoverTargets<'a>,
+    out: &mut Vec<CodeBlock<'a>>,
+) {
+    match node {
+        NorgAST::CarryoverTag {
+            name,
+            parameters,
+            next_object,
+            ..
+        } => {
+            if is_tangle_tag(name) {
+                carried.add(name, parameters);
+            }
+            collect_node(next_object, carried, out);
+        }
+        NorgAST::VerbatimRangedTag {
+            name,
+            parameters,
+            content,
+        } => carried.apply(name, parameters, content, out),
+        NorgAST::Heading { content, .. } => {
+            carried.warn_ignored();
+            collect_blocks(content, out);
+        }
+        NorgAST::RangedTag { content, .. } => {
+            carried.warn_ignored();
+            collect_flat_blocks(content, out);
+        }
+        _ => carried.warn_ignored(),
+    }
+}
+
+fn collect_flat_blocks<'a>(nodes: &'a [NorgASTFlat], out: &mut Vec<CodeBlock<'a>>) {
+    let mut index = 0;
+    while index < nodes.len() {
+        if let Some(next) = flat_comment_end(nodes, index) {
+            index = next;
+            continue;
+        }
+        collect_flat_node(&nodes[index], CarryoverTargets::default(), out);
+        index += 1;
+    }
+}
+
+fn collect_flat_node<'a>(
+    node: &'a NorgASTFlat,
+    mut carried: CarryoverTargets<'a>,
+    out: &mut Vec<CodeBlock<'a>>,
+) {
+    match node {
+        NorgASTFlat::CarryoverTag {
+            name,
+            parameters,
+            next_object,
+            ..
+        } => {
+            if is_tangle_tag(name) {
+                carried.add(name, parameters);
+            }
+            collect_flat_node(next_object, carried, out);
+        }
+        NorgASTFlat::VerbatimRangedTag {
+            name,
+            parameters,
+            content,
+        } => carried.apply(name, parameters, content, out),
+        NorgASTFlat::RangedTag { content, .. } => {
+            carried.warn_ignored();
+            collect_flat_blocks(content, out);
+        }
+        _ => carried.warn_ignored(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn document_targets_map_languages_and_fall_back() {
+        let ast = rust_norg::parse_tree(
+            "@document.meta\ntangle: {\n  languages: {\n    rust: src/main.rs\n  }\n}\n@end\n",
+        )
+        .expect("valid norg");
+        let targets = DocumentTargets::new(&ast);
+        assert_eq!(targets.get("rust"), Some("src/main.rs"));
+        assert_eq!(targets.get("lua"), None);
+
+        let targets = DocumentTargets {
+            all: Some("all.txt".into()),
+            ..Default::default()
+        };
+        assert_eq!(targets.get("lua"), Some("all.txt"));
+    }
+}
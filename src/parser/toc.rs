@@ -1,5 +1,5 @@
 use crate::ast_handlers::{document_ids, visit_visible_headings};
-use crate::segments::heading_html_and_id;
+use crate::segments::heading_parts;
 use crate::types::TocEntry;
 use rust_norg::NorgAST;
 
@@ -7,12 +7,13 @@ pub fn extract_toc(ast: &[NorgAST]) -> Vec<TocEntry> {
     let mut toc = Vec::new();
     let mut ids = document_ids(ast);
     visit_visible_headings(ast, &mut |level, title| {
-        let (title, id, level) = heading_html_and_id(title, level, &mut ids);
-        if !id.is_empty() {
+        let heading = heading_parts(title, level, &mut ids);
+        if !heading.id.is_empty() {
             toc.push(TocEntry {
-                level: level as u32,
-                title,
-                id,
+                level: heading.level as u32,
+                title: heading.html,
+                id: heading.id,
+                number: heading.number,
             });
         }
     });
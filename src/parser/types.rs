@@ -39,12 +39,105 @@ impl fmt::Display for OutputMode {
     }
 }
 
+/// How heading and footnote titles become anchor ids.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum SlugStyle {
+    /// Lowercased Unicode alphanumerics joined by single dashes.
+    standard,
+    /// GitHub's heading anchors: lowercased, punctuation removed, every space
+    /// a dash (so runs of spaces are not collapsed).
+    github,
+    /// Like `standard`, with Latin letters transliterated to ASCII and other
+    /// non-ASCII letters dropped.
+    ascii,
+    /// Like `standard`, without lowercasing.
+    preserve,
+}
+
+/// Which side of the heading text a permalink sits on.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum PermalinkPosition {
+    before,
+    after,
+}
+
+/// A self-link rendered inside every heading that has an id.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct PermalinkOptions {
+    /// Link text; defaults to `#`.
+    pub symbol: Option<String>,
+    /// Defaults to `after`.
+    pub position: Option<PermalinkPosition>,
+}
+
+/// What happens to headings deeper than HTML's `<h6>`.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum HeadingOverflow {
+    /// Render them as `<h6>`.
+    clamp,
+    /// Render them as `<div role="heading" aria-level="N">`, keeping the depth
+    /// for assistive technology.
+    aria,
+}
+
+/// What kind of reference pointed at a local asset.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum AssetKind {
+    /// An image source.
+    image,
+    /// A link to a file that isn't a page (`{:report.pdf:}`).
+    link,
+    video,
+    audio,
+    /// A caption track of a video or audio tag.
+    track,
+}
+
+impl AssetKind {
+    /// What the asset is, for diagnostics.
+    pub fn noun(self) -> &'static str {
+        match self {
+            Self::image => "image",
+            Self::link => "link",
+            Self::video => "video",
+            Self::audio => "audio",
+            Self::track => "caption track",
+        }
+    }
+}
+
+/// A local file the rendered HTML refers to, with the placeholder standing in
+/// for its URL. Collected with `asset_placeholders`.
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct AssetReference {
+    pub kind: AssetKind,
+    /// Path relative to the document, as an import specifier (`./img/a.png`).
+    pub path: String,
+    /// Token in the HTML to replace with the asset's resolved URL.
+    pub placeholder: String,
+    /// Index of the `html_parts` entry holding the placeholder.
+    pub part: u32,
+}
+
 #[napi(object)]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct TocEntry {
     pub level: u32,
     pub title: String,
     pub id: String,
+    /// Section number (`1.2`), present when numbering is on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<String>,
 }
 
 /// An embed block extracted from an @embed tag
@@ -58,3 +151,74 @@ pub struct EmbedComponent {
     /// Raw component code (user writes full component with imports)
     pub code: String,
 }
+
+/// Rendering options for `parse_norg`. Every field is optional; an unset field
+/// keeps the default rendering.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// URL schemes links and images may use (e.g. `["http", "https",
+    /// "mailto"]`). Site-relative URLs are always allowed, protocol-relative
+    /// ones only alongside `http` or `https`. Unset allows any scheme the
+    /// script-scheme denylist does.
+    pub allowed_schemes: Option<Vec<String>>,
+    /// Runs the rendered HTML, including `@embed html` blocks, through a tag
+    /// and attribute allowlist. Unset leaves the HTML as rendered.
+    pub sanitize: Option<SanitizeOptions>,
+    /// Slug algorithm for heading and footnote ids and the links to them.
+    /// Defaults to `standard`.
+    pub slug_style: Option<SlugStyle>,
+    /// Prepended to every generated heading and footnote id (and so to the TOC
+    /// and same-document links), keeping ids unique when several documents
+    /// share a page. Only letters, digits, `-` and `_`; anything else is
+    /// reported and ignored.
+    pub id_prefix: Option<String>,
+    /// Adds a `heading-anchor` self-link to each heading. Unset renders none.
+    pub permalink: Option<PermalinkOptions>,
+    /// Added to every heading level, for documents placed under an existing
+    /// page heading (`1` makes `*` render as `<h2>`). TOC levels shift too.
+    pub heading_offset: Option<u32>,
+    /// Rendering for levels past 6, whether written or reached through
+    /// `heading_offset`. Defaults to `aria`.
+    pub heading_overflow: Option<HeadingOverflow>,
+    /// Wraps each heading and its scope in `<section aria-labelledby="id">`.
+    pub sections: Option<bool>,
+    /// Renders headings at this Norg level or deeper as collapsed
+    /// `<details>`, like a `+collapsed` carryover on each. Unset collapses
+    /// only tagged headings.
+    pub collapse_level: Option<u32>,
+    /// Numbers headings hierarchically (`1`, `1.2`, `1.2.3`), rendered as a
+    /// `heading-number` span and exposed on TOC entries.
+    pub numbering: Option<bool>,
+    /// Ranged tag names rendered as callouts (`|warning Title`). Replaces the
+    /// default `note`, `tip`, `warning` and `danger`.
+    pub callouts: Option<Vec<String>>,
+    /// Path of the document being parsed. Relative image sources are resolved
+    /// against its directory to read their intrinsic `width` and `height`.
+    /// Unset reads no files.
+    pub file_path: Option<String>,
+    /// Directory that `@code file=…` includes and image sizes are read from,
+    /// after symlinks and `..` are resolved. Defaults to the document's
+    /// directory.
+    pub include_root: Option<String>,
+    /// Replaces the URLs of local images and linked files with placeholders,
+    /// listed in the result's `assets`, so a bundler can import each file and
+    /// swap in its final URL.
+    pub asset_placeholders: Option<bool>,
+    /// Adds each code block's unhighlighted text as `data-code` on its
+    /// `<pre>`, for copy buttons.
+    pub copy_code: Option<bool>,
+}
+
+/// Allowlists for HTML sanitization. Entries ending in `*` match by prefix
+/// (`data-*`). Event handlers and `style` are always removed, and URL
+/// attributes go through the same scheme checks as links.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct SanitizeOptions {
+    /// Elements kept; defaults to the ones this renderer emits.
+    pub allowed_tags: Option<Vec<String>>,
+    /// Attributes kept on allowed elements; defaults to the ones this renderer
+    /// emits plus `data-*` and `aria-*`.
+    pub allowed_attributes: Option<Vec<String>>,
+}
@@ -1,12 +1,51 @@
+use crate::types::{AssetKind, SlugStyle};
+use std::path::{Path, PathBuf};
+use unicode_categories::UnicodeCategories;
+
 /// Slugifies arbitrary text: lowercase alphanumerics joined by single dashes,
 /// with no leading or trailing dash. Lowercases via `str::to_lowercase` so
 /// context-sensitive mappings (e.g. Greek word-final Σ → ς) match the rendered
 /// text; `char::to_lowercase` would emit a different codepoint here and break
 /// inbound anchor links.
 pub fn into_slug(text: &str) -> String {
-    let lowered = text.to_lowercase();
-    let mut slug = String::with_capacity(lowered.len());
-    for c in lowered.chars() {
+    join_alphanumeric(&text.to_lowercase())
+}
+
+/// Slugifies `text` with the given algorithm. Every anchor id and every link
+/// to one goes through here, so a style applies to both ends of a link.
+/// Whether `text` can sit in an `id` or a `#` link unescaped: ASCII letters,
+/// digits, `-` and `_` only.
+pub fn is_plain_id(text: &str) -> bool {
+    text.chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+}
+
+pub fn slugify(text: &str, style: SlugStyle) -> String {
+    match style {
+        SlugStyle::standard => into_slug(text),
+        SlugStyle::github => github_slug(text),
+        SlugStyle::ascii => {
+            let lowered = text.to_lowercase();
+            let mut ascii = String::with_capacity(lowered.len());
+            for c in lowered.chars() {
+                match transliterate(c) {
+                    Some(latin) => ascii.push_str(latin),
+                    None if c.is_ascii() => ascii.push(c),
+                    // Unmapped letters drop out of their word; combining
+                    // marks (left behind by e.g. `İ`) go with them.
+                    None if c.is_alphanumeric() || ('\u{300}'..='\u{36f}').contains(&c) => {}
+                    None => ascii.push(' '),
+                }
+            }
+            join_alphanumeric(&ascii)
+        }
+        SlugStyle::preserve => join_alphanumeric(text),
+    }
+}
+
+fn join_alphanumeric(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.chars() {
         if c.is_alphanumeric() {
             slug.push(c);
         } else if !slug.is_empty() && !slug.ends_with('-') {
@@ -19,6 +58,54 @@ pub fn into_slug(text: &str) -> String {
     slug
 }
 
+/// github-slugger's rule: drop everything but letters, combining marks,
+/// numbers, connector punctuation (`_`), `-` and spaces, then turn each space
+/// into a dash. Heading text is trimmed first, as GitHub's Markdown parser
+/// does.
+fn github_slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' => Some(c),
+            c if c.is_alphanumeric() || c.is_mark() || c.is_punctuation_connector() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// ASCII spelling of a lowercase Latin-1 or Latin Extended-A letter.
+fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĳ' => "ij",
+        'ĵ' => "j",
+        'ķ' | 'ĸ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' | 'ŉ' | 'ŋ' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ſ' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    })
+}
+
 /// How a link target addresses its destination. Rewriting, new-tab hardening
 /// and scheme blocking all read from this one classification, so they cannot
 /// disagree about what a given target is.
@@ -77,12 +164,7 @@ fn is_scheme_shaped(scheme: &str) -> bool {
 /// URL parsing — leading C0 controls stripped, tab/newline ignored anywhere —
 /// so `" java\nscript:"` cannot slip past.
 pub fn has_unsafe_scheme(href: &str) -> bool {
-    let normalized: String = href
-        .trim_start_matches(|c: char| c.is_ascii_control() || c == ' ')
-        .chars()
-        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
-        .collect();
-
+    let normalized = normalize_url(href);
     let UrlKind::Scheme(scheme) = UrlKind::of(&normalized) else {
         return false;
     };
@@ -97,6 +179,75 @@ pub fn has_unsafe_scheme(href: &str) -> bool {
     }
 }
 
+/// True when `href` uses a scheme on `allowed` (case-insensitive, a trailing
+/// `:` on an entry ignored). Site-relative URLs carry no scheme and always
+/// pass; `//host` inherits the page's, so it passes only if `http` or `https`
+/// does. Normalised like [`has_unsafe_scheme`], so the two can't classify the
+/// same URL differently.
+pub fn is_allowed_scheme(href: &str, allowed: &[String]) -> bool {
+    let allows = |scheme: &str| {
+        allowed
+            .iter()
+            .any(|entry| entry.trim_end_matches(':').eq_ignore_ascii_case(scheme))
+    };
+    match UrlKind::of(&normalize_url(href)) {
+        UrlKind::SiteRelative => true,
+        UrlKind::ProtocolRelative => allows("http") || allows("https"),
+        UrlKind::Scheme(scheme) => allows(scheme),
+    }
+}
+
+/// Why `url` must not be emitted as a link or media source, if it mustn't. The
+/// script-scheme denylist always applies; a configured `allowed_schemes`
+/// narrows what's left.
+pub fn refused_url(url: &str) -> Option<&'static str> {
+    if has_unsafe_scheme(url) {
+        return Some("unsafe URL scheme");
+    }
+    crate::options::with(|options| match &options.allowed_schemes {
+        Some(allowed) if !is_allowed_scheme(url, allowed) => Some("disallowed URL scheme"),
+        _ => None,
+    })
+}
+
+/// The `src` for a fetched resource (an image, video, poster or track), or
+/// `None` with a diagnostic when the URL policy refuses it — the whole element
+/// goes, like a refused link. Only a bare relative path needs `./`; rooted,
+/// `//host` and scheme'd sources already resolve.
+pub fn resource_src(path: &str, kind: AssetKind) -> Option<String> {
+    if let Some(reason) = refused_url(path) {
+        crate::diagnostics::warn(format!("dropping {} with {reason}: {path}", kind.noun()));
+        return None;
+    }
+    let src = if UrlKind::of(path).is_site_relative() && !path.starts_with('/') {
+        format!("./{path}")
+    } else {
+        path.to_string()
+    };
+    Some(crate::assets::reference(&src, kind))
+}
+
+/// `path` resolved against the directory of the document being parsed, or
+/// `None` when the `file_path` option doesn't say where that is.
+pub fn document_relative(path: &str) -> Option<PathBuf> {
+    let file_path = crate::options::with(|options| options.file_path.clone())?;
+    Some(
+        Path::new(&file_path)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(path),
+    )
+}
+
+/// Mirrors browser URL parsing: leading C0 controls and spaces stripped,
+/// tab/newline ignored anywhere.
+fn normalize_url(href: &str) -> String {
+    href.trim_start_matches(|c: char| c.is_ascii_control() || c == ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,6 +329,36 @@ mod tests {
         assert!(!has_unsafe_scheme("foo?x=a:b"));
     }
 
+    #[test]
+    fn allowlist_admits_only_listed_schemes() {
+        let allowed = [
+            "http".to_string(),
+            "HTTPS".to_string(),
+            "mailto:".to_string(),
+        ];
+        assert!(is_allowed_scheme("https://example.com", &allowed));
+        assert!(is_allowed_scheme("HTTP://example.com", &allowed));
+        assert!(is_allowed_scheme("mailto:a@b.com", &allowed));
+        // No scheme to check: relative paths and fragments always pass.
+        assert!(is_allowed_scheme("docs/readme.html", &allowed));
+        assert!(is_allowed_scheme("#section", &allowed));
+        assert!(is_allowed_scheme("path/to:file", &allowed));
+        // `//host` takes the page's scheme, so it rides on http/https.
+        assert!(is_allowed_scheme("//cdn.example.com/x", &allowed));
+        assert!(!is_allowed_scheme(
+            "//cdn.example.com/x",
+            &["mailto".to_string()]
+        ));
+        assert!(!is_allowed_scheme("ftp://ftp.gnu.org/gnu/", &allowed));
+        assert!(!is_allowed_scheme("tel:+15551234567", &allowed));
+        assert!(!is_allowed_scheme(
+            "data:image/png;base64,iVBORw0KGgo=",
+            &allowed
+        ));
+        // Same normalisation as the denylist.
+        assert!(!is_allowed_scheme(" f\ttp://host", &allowed));
+    }
+
     #[test]
     fn test_into_slug() {
         assert_eq!(into_slug("Hello World"), "hello-world");
@@ -190,4 +371,24 @@ mod tests {
         // matching `str::to_lowercase`'s context-aware mapping.
         assert_eq!(into_slug("ΛΟΓΟΣ"), "λογος");
     }
+
+    #[test]
+    fn slug_styles() {
+        use SlugStyle::*;
+
+        assert_eq!(slugify("C++ & Rust", standard), "c-rust");
+        // GitHub keeps one dash per space, and `_`/`-` as written.
+        assert_eq!(slugify("C++ & Rust", github), "c--rust");
+        assert_eq!(slugify(" Hello, World! ", github), "hello-world");
+        assert_eq!(
+            slugify("snake_case -- Thing", github),
+            "snake_case----thing"
+        );
+        // Combining marks stay, so a decomposed é keeps its accent.
+        assert_eq!(slugify("Cafe\u{301} Menu", github), "cafe\u{301}-menu");
+        assert_eq!(slugify("Über Café", ascii), "uber-cafe");
+        assert_eq!(slugify("Straße — Łódź", ascii), "strasse-lodz");
+        assert_eq!(slugify("İstanbul 日本", ascii), "istanbul");
+        assert_eq!(slugify("Getting Started!", preserve), "Getting-Started");
+    }
 }
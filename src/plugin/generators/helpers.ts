@@ -13,6 +13,14 @@ export function dedent(strings: TemplateStringsArray, ...values: unknown[]): str
     .join('\n');
 }
 
+// Collapsed sections are `<details>`; the imported module opens the ones
+// around a linked heading.
+export function addSectionsImport(htmlParts: string[]): string | null {
+  return htmlParts.some(part => part.includes('<details'))
+    ? 'import "virtual:norg-sections";'
+    : null;
+}
+
 export function addEmbedImports(
   embedComponents: EmbedComponent[],
   filePath?: string
@@ -1,5 +1,5 @@
 import type { NorgParseResult, EmbedComponent } from '@parser';
-import { dedent } from './helpers';
+import { dedent, addSectionsImport } from './helpers';
 
 function mergeEmbeds(htmlParts: string[], embeds: EmbedComponent[]): string {
   return htmlParts
@@ -15,6 +15,7 @@ export function generateHtml(
   const html = embedCss ? `<style>${embedCss}</style>${raw}` : raw;
   return dedent`
     ${css ? 'import "virtual:norg-arborium.css";' : null}
+    ${addSectionsImport(htmlParts)}
 
     export const metadata = ${JSON.stringify(metadata ?? {})};
     export const html = ${JSON.stringify(html)};
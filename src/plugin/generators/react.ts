@@ -1,5 +1,5 @@
 import type { NorgParseResult } from '@parser';
-import { dedent, addEmbedImports } from './helpers';
+import { dedent, addEmbedImports, addSectionsImport } from './helpers';
 
 export function generateReact(
   { htmlParts, metadata, toc, embedComponents = [], embedCss = '' }: NorgParseResult,
@@ -15,6 +15,7 @@ export function generateReact(
 
   return dedent`
     ${css ? 'import "virtual:norg-arborium.css";' : null}
+    ${addSectionsImport(htmlParts)}
     ${embedCss && filePath ? `import 'virtual:norg-css:${filePath}';` : null}
     ${addEmbedImports(embedComponents, filePath)}
 
@@ -1,12 +1,13 @@
 import type { NorgParseResult } from '@parser';
-import { dedent, addEmbedImports } from './helpers';
+import { dedent, addEmbedImports, addSectionsImport } from './helpers';
 
 export function generateSvelte(
   { htmlParts, metadata, toc, embedComponents = [], embedCss = '' }: NorgParseResult,
   css: string,
   filePath?: string
 ): string {
-  const hasImports = !!(css || embedComponents.length);
+  const sectionsImport = addSectionsImport(htmlParts);
+  const hasImports = !!(css || embedComponents.length || sectionsImport);
   const body = htmlParts
     .flatMap((part, i) => [
       `{@html ${JSON.stringify(part)}}`,
@@ -21,6 +22,7 @@ export function generateSvelte(
     </script>
     ${hasImports ? '<script lang="ts">' : null}
       ${css ? 'import "virtual:norg-arborium.css";' : null}
+      ${sectionsImport}
       ${addEmbedImports(embedComponents, filePath)}
     ${hasImports ? '</script>' : null}
     ${embedCss ? `{@html ${JSON.stringify(`<style>${embedCss}</style>`)}}` : null}
@@ -1,5 +1,5 @@
 import type { NorgParseResult } from '@parser';
-import { dedent, addEmbedImports } from './helpers';
+import { dedent, addEmbedImports, addSectionsImport } from './helpers';
 
 export function generateVue(
   { htmlParts, metadata, toc, embedComponents = [], embedCss = '' }: NorgParseResult,
@@ -23,6 +23,7 @@ export function generateVue(
     </script>
     <script setup lang="ts">
     ${css ? 'import "virtual:norg-arborium.css";' : null}
+    ${addSectionsImport(htmlParts)}
     ${addEmbedImports(embedComponents, filePath)}
     ${
       embedComponents.length > 0
@@ -1,5 +1,12 @@
 export { norgPlugin, type ArboriumConfig, type NorgPluginOptions } from './plugin';
-export type { NorgParseResult, TocEntry, EmbedComponent } from '@parser';
+export { tangleNorg } from '@parser';
+export type {
+  NorgParseResult,
+  ParseOptions,
+  TangleResult,
+  TocEntry,
+  EmbedComponent,
+} from '@parser';
 export type { HtmlModule } from './types/html';
 export type { SvelteModule } from './types/svelte';
 export type { ReactModule } from './types/react';
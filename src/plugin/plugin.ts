@@ -8,7 +8,13 @@ import {
   type ModuleNode,
   type Plugin,
 } from 'vite';
-import { parseNorg, getThemeCss, OutputMode } from '@parser';
+import {
+  parseNorg,
+  getThemeCss,
+  OutputMode,
+  type AssetReference,
+  type ParseOptions,
+} from '@parser';
 import { generateOutput, type GeneratorMode } from './generators';
 
 export type ArboriumConfig =
@@ -22,11 +28,40 @@ export interface NorgPluginOptions {
   arboriumConfig?: ArboriumConfig;
   componentDir?: string;
   components?: Record<string, string>;
+  parser?: ParseOptions;
+  // Passes each document's path to the parser, with Vite's root as the include root.
+  readFiles?: boolean;
 }
 
 const VIRTUAL_CSS_ID = 'virtual:norg-arborium.css';
 const RESOLVED_VIRTUAL_CSS_ID = `\0${VIRTUAL_CSS_ID}`;
 
+const VIRTUAL_SECTIONS_ID = 'virtual:norg-sections';
+const RESOLVED_VIRTUAL_SECTIONS_ID = `\0${VIRTUAL_SECTIONS_ID}`;
+
+// Opens the collapsed sections (`<details>`) around the heading a link points
+// at: on a click, before the browser scrolls to it, and on load and history
+// navigation, after.
+const SECTIONS_SCRIPT = `
+function openSectionsAround(hash, scroll) {
+  const target = hash.length > 1 && document.getElementById(decodeURIComponent(hash.slice(1)));
+  if (!target) return;
+  for (let details = target.closest('details'); details; ) {
+    details.open = true;
+    details = details.parentElement && details.parentElement.closest('details');
+  }
+  if (scroll) target.scrollIntoView();
+}
+if (typeof document !== 'undefined') {
+  document.addEventListener('click', event => {
+    const link = event.target instanceof Element && event.target.closest('a[href^="#"]');
+    if (link) openSectionsAround(link.getAttribute('href'), false);
+  });
+  addEventListener('hashchange', () => openSectionsAround(location.hash, true));
+  setTimeout(() => openSectionsAround(location.hash, true));
+}
+`;
+
 const VIRTUAL_DOC_CSS_PREFIX = 'virtual:norg-css:';
 const RESOLVED_VIRTUAL_DOC_CSS_PREFIX = `\0${VIRTUAL_DOC_CSS_PREFIX}`;
 
@@ -100,13 +135,35 @@ function injectComponentImports(
     );
   }
 
-  if (mode === OutputMode.react) {
+  if (mode === OutputMode.react || mode === OutputMode.html) {
     return imports + '\n' + code;
   }
 
   return code;
 }
 
+// Placeholders only ever land inside the JSON string literals generators emit
+// for HTML, so each is spliced out of its literal as an imported URL.
+function importAssets(
+  code: string,
+  assets: AssetReference[],
+  mode: GeneratorMode,
+  basePath: string
+): string {
+  const imports = new Map<string, string>();
+  const names = new Map<string, string>();
+  let linked = code;
+  for (const { path, placeholder } of assets) {
+    if (!linked.includes(placeholder)) continue;
+    const file = resolve(dirname(basePath), path);
+    const name = names.get(file) ?? `__norgAsset${names.size}`;
+    names.set(file, name);
+    imports.set(name, file);
+    linked = linked.replaceAll(placeholder, `" + ${name} + "`);
+  }
+  return injectComponentImports(linked, imports, mode);
+}
+
 export function norgPlugin(options: NorgPluginOptions): Plugin {
   const {
     include,
@@ -115,6 +172,8 @@ export function norgPlugin(options: NorgPluginOptions): Plugin {
     arboriumConfig,
     componentDir,
     components: explicitComponents,
+    parser: parseOptions,
+    readFiles,
   } = options;
 
   // The one option that fails silently — an unknown mode matches no generator
@@ -134,7 +193,10 @@ export function norgPlugin(options: NorgPluginOptions): Plugin {
   const parseCache = new Map<string, Promise<ReturnType<typeof parseNorg>>>();
   const embedModuleIds = new Map<string, Set<string>>();
   const embedModules = new Map<string, { basePath: string; index: number }>();
+  // Files included into documents (`@code file=…`), to the documents including them.
+  const includedBy = new Map<string, Set<string>>();
   let components = new Map<string, string>();
+  let root: string | undefined;
 
   function trackModule(filePath: string, moduleId: string) {
     const ids = embedModuleIds.get(filePath) ?? new Set<string>();
@@ -150,9 +212,22 @@ export function norgPlugin(options: NorgPluginOptions): Plugin {
     if (!pending) {
       const fresh = readFile(filePath, 'utf-8')
         .then(content => {
-          const result = parseNorg(content, mode);
+          const options = readFiles
+            ? { filePath, includeRoot: root, ...parseOptions }
+            : parseOptions;
+          const result = parseNorg(content, mode, options);
           if (parseCache.get(filePath) !== fresh) return cachedParse(filePath, warn);
           result.diagnostics?.forEach(warn);
+          // Drop what the previous parse included, so removed includes stop re-rendering it.
+          includedBy.forEach((documents, dependency) => {
+            documents.delete(filePath);
+            if (documents.size === 0) includedBy.delete(dependency);
+          });
+          result.dependencies.forEach(dependency => {
+            const documents = includedBy.get(dependency) ?? new Set<string>();
+            documents.add(filePath);
+            includedBy.set(dependency, documents);
+          });
           return result;
         })
         .catch(error => {
@@ -184,6 +259,10 @@ export function norgPlugin(options: NorgPluginOptions): Plugin {
     name: 'vite-plugin-norg',
     enforce: 'pre',
 
+    configResolved(config) {
+      root = config.root;
+    },
+
     async buildStart() {
       if (resolvedComponentDir) {
         components = await scanComponentDir(resolvedComponentDir, mode);
@@ -204,6 +283,10 @@ export function norgPlugin(options: NorgPluginOptions): Plugin {
         return RESOLVED_VIRTUAL_CSS_ID;
       }
 
+      if (id === VIRTUAL_SECTIONS_ID) {
+        return RESOLVED_VIRTUAL_SECTIONS_ID;
+      }
+
       if (id.startsWith(VIRTUAL_DOC_CSS_PREFIX)) {
         return `\0${id}.css`;
       }
@@ -234,6 +317,10 @@ export function norgPlugin(options: NorgPluginOptions): Plugin {
         return css;
       }
 
+      if (id === RESOLVED_VIRTUAL_SECTIONS_ID) {
+        return SECTIONS_SCRIPT;
+      }
+
       if (id.startsWith(RESOLVED_VIRTUAL_DOC_CSS_PREFIX) && id.endsWith('.css')) {
         const filePath = id.slice(RESOLVED_VIRTUAL_DOC_CSS_PREFIX.length, -4);
         trackModule(filePath, id);
@@ -273,7 +360,13 @@ export function norgPlugin(options: NorgPluginOptions): Plugin {
 
       try {
         const result = await parse(basePath);
-        const code = generateOutput(outputMode, result, css, basePath);
+        result.dependencies.forEach(dependency => this.addWatchFile(dependency));
+        const code = importAssets(
+          generateOutput(outputMode, result, css, basePath),
+          result.assets,
+          outputMode,
+          basePath
+        );
         return {
           code,
           map: { version: 3, sources: [basePath], names: [], mappings: '' },
@@ -304,6 +397,15 @@ export function norgPlugin(options: NorgPluginOptions): Plugin {
         return;
       }
 
+      const documents = includedBy.get(ctx.file);
+      if (documents) {
+        const trackedIds = [...documents].flatMap(document => {
+          parseCache.delete(document);
+          return [...(embedModuleIds.get(document) ?? [])];
+        });
+        return [...ctx.modules, ...invalidateModules(ctx, trackedIds)];
+      }
+
       if (!ctx.file.endsWith('.norg')) return;
       parseCache.delete(ctx.file);
 
@@ -1,11 +1,16 @@
 use insta::assert_yaml_snapshot;
 use std::fs;
 use vite_plugin_norg_parser::{
-    NorgParseResult, extract_metadata, extract_toc, parse_norg, transform,
+    AssetKind, HeadingOverflow, NorgParseResult, ParseOptions, PermalinkOptions, PermalinkPosition,
+    SanitizeOptions, SlugStyle, extract_metadata, extract_toc, parse_norg, tangle_norg, transform,
 };
 
 fn parse(content: &str) -> NorgParseResult {
-    parse_norg(content.to_string(), None).expect("failed to parse norg")
+    parse_norg(content.to_string(), None, None).expect("failed to parse norg")
+}
+
+fn parse_with(content: &str, options: ParseOptions) -> NorgParseResult {
+    parse_norg(content.to_string(), None, Some(options)).expect("failed to parse norg")
 }
 
 #[test]
@@ -69,7 +74,7 @@ fn embed_errors_report_the_ast_declaration() {
             "embed #1",
         ),
     ] {
-        let error = match parse_norg(content.to_string(), Some("html".to_string())) {
+        let error = match parse_norg(content.to_string(), Some("html".to_string()), None) {
             Ok(_) => panic!("expected embed error"),
             Err(error) => error,
         };
@@ -402,7 +407,7 @@ fn mixed_marker_grandchildren_stay_under_their_parent() {
 #[test]
 fn embed_component_indexes_ignore_css_declarations() {
     let content = "@embed css\n.foo {}\n@end\n@embed svelte\n<div>one</div>\n@end\n@embed svelte\n<div>two</div>\n@end\n";
-    let result = parse_norg(content.to_string(), Some("svelte".to_string())).unwrap();
+    let result = parse_norg(content.to_string(), Some("svelte".to_string()), None).unwrap();
     let indexes: Vec<_> = result
         .embed_components
         .iter()
@@ -410,3 +415,1087 @@ fn embed_component_indexes_ignore_css_declarations() {
         .collect();
     assert_eq!(indexes, [0, 1]);
 }
+
+#[test]
+fn scheme_allowlist_drops_unlisted_links_and_images() {
+    let options = ParseOptions {
+        allowed_schemes: Some(vec!["https".into(), "mailto".into()]),
+        ..Default::default()
+    };
+    let result = parse_with(
+        "{https://example.com}[Web] {ftp://host/file}[Ftp] {mailto:a@b.com}[Mail] {:docs/readme.norg:}[Doc]\n\n@image ftp://host/a.png\nalt\n@end\n\n@image relative/a.png\nalt\n@end\n",
+        options,
+    );
+    let html = result.html_parts.concat();
+    assert!(html.contains(r#"href="https://example.com""#), "{html}");
+    assert!(html.contains(r#"href="mailto:a@b.com""#), "{html}");
+    assert!(html.contains(r#"href="docs/readme.html""#), "{html}");
+    assert!(!html.contains("ftp://"), "{html}");
+    assert!(html.contains("Ftp"), "{html}");
+    assert!(html.contains(r#"<img src="./relative/a.png""#), "{html}");
+
+    let diagnostics = result.diagnostics.unwrap_or_default();
+    assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+    assert!(diagnostics[0].contains("dropping link with disallowed URL scheme"));
+    assert!(diagnostics[1].contains("dropping image with disallowed URL scheme"));
+}
+
+#[test]
+fn sanitize_cleans_embedded_html_and_reports_removals() {
+    let content =
+        "* Title\n@embed html\n<div onmouseover=\"x()\"><script>steal()</script>ok</div>\n@end\n";
+    let options = ParseOptions {
+        sanitize: Some(SanitizeOptions::default()),
+        ..Default::default()
+    };
+    let result = parse_norg(content.to_string(), Some("html".to_string()), Some(options)).unwrap();
+    assert_eq!(result.html_parts[0], "<h1 id=\"title\">Title</h1>\n");
+    assert_eq!(result.embed_components[0].code.trim(), "<div>ok</div>");
+    let diagnostics = result.diagnostics.unwrap_or_default();
+    assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+    assert!(diagnostics.iter().all(|d| d.starts_with("sanitize: ")));
+}
+
+#[test]
+fn slug_styles_apply_to_ids_toc_and_links() {
+    let content = "* C++ & Rust\n* C++ & Rust\n{* C++ & Rust}\n";
+    for (style, ids) in [
+        (SlugStyle::standard, ["c-rust", "c-rust-1"]),
+        (SlugStyle::github, ["c--rust", "c--rust-1"]),
+        (SlugStyle::preserve, ["C-Rust", "C-Rust-1"]),
+    ] {
+        let options = ParseOptions {
+            slug_style: Some(style),
+            ..Default::default()
+        };
+        let result = parse_with(content, options);
+        let html = result.html_parts.concat();
+        let toc: Vec<_> = result.toc.iter().map(|entry| entry.id.as_str()).collect();
+        assert_eq!(toc, ids);
+        assert!(html.contains(&format!("<h1 id=\"{}\">", ids[1])), "{html}");
+        assert!(html.contains(&format!("href=\"#{}\"", ids[0])), "{html}");
+    }
+}
+
+#[test]
+fn id_prefix_applies_to_every_generated_id() {
+    let options = ParseOptions {
+        id_prefix: Some("doc1-".into()),
+        ..Default::default()
+    };
+    let result = parse_with("* Setup\n{* Setup}\n{^ note}\n^ note\nbody\n", options);
+    let html = result.html_parts.concat();
+    assert!(html.contains(r#"<h1 id="doc1-setup">Setup</h1>"#), "{html}");
+    assert!(
+        html.contains(r##"<a href="#doc1-setup">Setup</a>"##),
+        "{html}"
+    );
+    assert!(html.contains(r#"<aside id="doc1-footnote-note""#), "{html}");
+    assert!(html.contains(r##"href="#doc1-footnote-note""##), "{html}");
+    assert_eq!(result.toc[0].id, "doc1-setup");
+
+    let result = parse_with(
+        "* Setup\n",
+        ParseOptions {
+            id_prefix: Some("\"><x ".into()),
+            ..Default::default()
+        },
+    );
+    let html = result.html_parts.concat();
+    assert!(html.contains(r#"<h1 id="setup">Setup</h1>"#), "{html}");
+    assert_eq!(result.toc[0].id, "setup");
+    assert_eq!(
+        result.diagnostics.unwrap_or_default(),
+        ["idPrefix '\"><x ' may only contain letters, digits, '-' and '_' — ignored"]
+    );
+}
+
+#[test]
+fn carryover_attributes_reach_their_blocks() {
+    let content = "\
++id intro
+* Setup
++class lead
++attr data-track=hero
+Welcome.
++class steps
+- one
+- two
+* Intro
+{* Setup}
+";
+    let result = parse_norg(content.to_string(), None, None).unwrap();
+    let html = result.html_parts.concat();
+    assert!(html.contains(r#"<h1 id="intro">Setup</h1>"#), "{html}");
+    assert!(
+        html.contains(r#"<p class="lead" data-track="hero">Welcome.</p>"#),
+        "{html}"
+    );
+    assert!(html.contains(r#"<ul class="steps">"#), "{html}");
+    // The custom id is claimed first, so the generated one steps aside.
+    assert!(html.contains(r#"<h1 id="intro-1">Intro</h1>"#), "{html}");
+    assert!(html.contains(r##"<a href="#intro">Setup</a>"##), "{html}");
+    let toc: Vec<_> = result.toc.iter().map(|entry| entry.id.as_str()).collect();
+    assert_eq!(toc, ["intro", "intro-1"]);
+    assert!(result.diagnostics.unwrap_or_default().is_empty());
+}
+
+#[test]
+fn permalinks_follow_symbol_and_position() {
+    let content = "* Setup\n* @@@\n";
+    let after = parse_with(
+        content,
+        ParseOptions {
+            permalink: Some(PermalinkOptions::default()),
+            ..Default::default()
+        },
+    );
+    let html = after.html_parts.concat();
+    assert!(
+        html.contains(
+            r##"<h1 id="setup">Setup <a class="heading-anchor" href="#setup" aria-label="Permalink">#</a></h1>"##
+        ),
+        "{html}"
+    );
+    // No id, nothing to link to.
+    assert_eq!(html.matches("heading-anchor").count(), 1, "{html}");
+    assert_eq!(after.toc[0].title, "Setup");
+
+    let before = parse_with(
+        content,
+        ParseOptions {
+            permalink: Some(PermalinkOptions {
+                symbol: Some("¶".into()),
+                position: Some(PermalinkPosition::before),
+            }),
+            ..Default::default()
+        },
+    );
+    assert!(
+        before.html_parts[0].contains(
+            r##"<h1 id="setup"><a class="heading-anchor" href="#setup" aria-label="Permalink">¶</a> Setup</h1>"##
+        ),
+        "{}",
+        before.html_parts[0]
+    );
+}
+
+#[test]
+fn heading_offset_shifts_tags_and_toc_together() {
+    let content = "* Title\n***** Deep\n****** Deeper\n";
+    let clamped = parse_with(
+        content,
+        ParseOptions {
+            heading_offset: Some(1),
+            heading_overflow: Some(HeadingOverflow::clamp),
+            ..Default::default()
+        },
+    );
+    let html = clamped.html_parts.concat();
+    assert!(html.contains(r#"<h2 id="title">Title</h2>"#), "{html}");
+    assert!(html.contains(r#"<h6 id="deep">Deep</h6>"#), "{html}");
+    assert!(html.contains(r#"<h6 id="deeper">Deeper</h6>"#), "{html}");
+    let levels: Vec<_> = clamped.toc.iter().map(|entry| entry.level).collect();
+    assert_eq!(levels, [2, 6, 6]);
+
+    let aria = parse_with(
+        content,
+        ParseOptions {
+            heading_offset: Some(1),
+            ..Default::default()
+        },
+    );
+    let html = aria.html_parts.concat();
+    assert!(
+        html.contains(r#"<div role="heading" aria-level="7" id="deeper">Deeper</div>"#),
+        "{html}"
+    );
+    let levels: Vec<_> = aria.toc.iter().map(|entry| entry.level).collect();
+    assert_eq!(levels, [2, 6, 7]);
+
+    // Headings inside ranged tags follow the same policy.
+    let html = parse_with(
+        "|details More\n****** Inside\n|end\n",
+        ParseOptions {
+            heading_offset: Some(1),
+            ..Default::default()
+        },
+    )
+    .html_parts
+    .concat();
+    assert!(
+        html.contains(r#"<div role="heading" aria-level="7">Inside</div>"#),
+        "{html}"
+    );
+}
+
+#[test]
+fn sections_close_at_heading_and_delimiter_scope() {
+    let options = || ParseOptions {
+        sections: Some(true),
+        ..Default::default()
+    };
+    let html = parse_with(
+        "* A\nintro\n** B\nb text\n* C\nc text\n---\nafter\n",
+        options(),
+    )
+    .html_parts
+    .concat();
+    assert_eq!(
+        html,
+        "<section aria-labelledby=\"a\">\n<h1 id=\"a\">A</h1>\n<p>intro</p>\n\
+         <section aria-labelledby=\"b\">\n<h2 id=\"b\">B</h2>\n<p>b text</p>\n\
+         </section>\n</section>\n\
+         <section aria-labelledby=\"c\">\n<h1 id=\"c\">C</h1>\n<p>c text</p>\n\
+         </section>\n<hr class=\"weak\" />\n<p>after</p>\n"
+    );
+
+    let html = parse_with("* A\n** B\n===\nafter\n", options())
+        .html_parts
+        .concat();
+    assert!(
+        html.ends_with("</section>\n</section>\n<hr class=\"strong\" />\n<p>after</p>\n"),
+        "{html}"
+    );
+
+    // html output concatenates the parts, so a section spans an embed.
+    let result = parse_norg(
+        "* A\n@embed html\n<b>x</b>\n@end\nafter\n".to_string(),
+        Some("html".to_string()),
+        Some(options()),
+    )
+    .unwrap();
+    assert_eq!(
+        result.html_parts,
+        [
+            "<section aria-labelledby=\"a\">\n<h1 id=\"a\">A</h1>\n",
+            "<p>after</p>\n</section>\n",
+        ]
+    );
+    assert!(result.diagnostics.unwrap_or_default().is_empty());
+
+    // Component modes render parts into separate containers, so sections
+    // close at embeds, and say so.
+    let result = parse_norg(
+        "* A\n@embed svelte\n<b>x</b>\n@end\nafter\n".to_string(),
+        Some("svelte".to_string()),
+        Some(options()),
+    )
+    .unwrap();
+    for part in &result.html_parts {
+        assert_eq!(
+            part.matches("<section").count(),
+            part.matches("</section>").count(),
+            "{part}"
+        );
+    }
+    assert_eq!(
+        result.diagnostics.unwrap_or_default(),
+        [
+            "embed #1 closes the sections around it early — svelte output renders the parts \
+             around an embed separately"
+        ]
+    );
+}
+
+#[test]
+fn collapsed_headings_render_as_details() {
+    let result = parse("* Guide\n+collapsed\n** Reference\nbody\n** Notes\nmore\n");
+    let html = result.html_parts.concat();
+    assert!(
+        html.contains(
+            "<details><summary><h2 id=\"reference\">Reference</h2></summary>\n<p>body</p>\n</details>\n<h2 id=\"notes\">"
+        ),
+        "{html}"
+    );
+    assert_eq!(result.toc[1].id, "reference");
+    assert!(result.diagnostics.unwrap_or_default().is_empty());
+
+    let options = ParseOptions {
+        sections: Some(true),
+        collapse_level: Some(2),
+        ..Default::default()
+    };
+    let html = parse_with("* Guide\n** Reference\nbody\n---\nafter\n", options)
+        .html_parts
+        .concat();
+    assert!(
+        html.contains(
+            "<section aria-labelledby=\"reference\">\n<details><summary><h2 id=\"reference\">Reference</h2></summary>\n<p>body</p>\n</details>\n</section>\n<hr class=\"weak\" />"
+        ),
+        "{html}"
+    );
+    assert!(!html.contains("<details><summary><h1"), "{html}");
+
+    // A weak delimiter leaves the unwrapped `** B`, not the `<details>` around it.
+    let html = parse("+collapsed\n* A\n** B\nb\n---\nstill a\n* C\n")
+        .html_parts
+        .concat();
+    assert!(
+        html.contains("<p>b</p>\n<hr class=\"weak\" />\n<p>still a</p>\n</details>\n<h1 id=\"c\">"),
+        "{html}"
+    );
+}
+
+#[test]
+fn numbering_skips_commented_headings() {
+    let options = ParseOptions {
+        numbering: Some(true),
+        ..Default::default()
+    };
+    let result = parse_with(
+        "* Intro\n** Scope\n+comment\n** Draft\n** Terms\n*** Words\n* Usage\n",
+        options,
+    );
+    let html = result.html_parts.concat();
+    assert!(
+        html.contains(r#"<h2 id="terms"><span class="heading-number">1.2</span> Terms</h2>"#),
+        "{html}"
+    );
+    let numbers: Vec<_> = result
+        .toc
+        .iter()
+        .map(|entry| (entry.id.as_str(), entry.number.as_deref()))
+        .collect();
+    assert_eq!(
+        numbers,
+        [
+            ("intro", Some("1")),
+            ("scope", Some("1.1")),
+            ("terms", Some("1.2")),
+            ("words", Some("1.2.1")),
+            ("usage", Some("2")),
+        ]
+    );
+    assert_eq!(result.toc[2].title, "Terms");
+    assert!(parse("* Intro\n").toc[0].number.is_none());
+
+    // A skipped level keeps counting when the heading between returns.
+    let options = ParseOptions {
+        numbering: Some(true),
+        ..Default::default()
+    };
+    let numbers: Vec<_> = parse_with("* A\n*** B\n** C\n*** D\n", options)
+        .toc
+        .into_iter()
+        .map(|entry| entry.number.unwrap_or_default())
+        .collect();
+    assert_eq!(numbers, ["1", "1.1", "1.2", "1.2.1"]);
+}
+
+#[test]
+fn math_renders_to_mathml() {
+    let result = parse("Area is $\\pi r^2$.\n\n@math\n\\frac{a}{b}\n@end\n");
+    let html = result.html_parts.concat();
+    assert!(
+        html.contains(
+            "<p>Area is <math><mrow><mi>π</mi><msup><mi>r</mi><mn>2</mn></msup></mrow></math>.</p>"
+        ),
+        "{html}"
+    );
+    assert!(
+        html.contains(
+            r#"<math display="block"><mfrac><mrow><mi>a</mi></mrow><mrow><mi>b</mi></mrow></mfrac></math>"#
+        ),
+        "{html}"
+    );
+    assert!(result.diagnostics.unwrap_or_default().is_empty());
+
+    let diagnostics = parse("$\\weird x$\n").diagnostics.unwrap_or_default();
+    assert_eq!(diagnostics, ["math: unsupported command '\\weird'"]);
+}
+
+#[test]
+fn standard_ranged_tags_render_their_content() {
+    let result = parse(
+        "|details Show more\nHidden *text*.\n- one\n- two\n|end\n\
+         |group\nGrouped.\n|end\n\
+         |example\n* Title\n|end\n",
+    );
+    let html = result.html_parts.concat();
+    assert!(
+        html.contains(
+            "<details><summary>Show more</summary>\n<p>Hidden <strong>text</strong>.</p>\n<ul>"
+        ),
+        "{html}"
+    );
+    assert!(html.contains("</ul>\n</details>"), "{html}");
+    assert!(
+        html.contains("<div class=\"group\">\n<p>Grouped.</p>\n</div>"),
+        "{html}"
+    );
+    assert!(
+        html.contains(
+            "<div class=\"example\">\n<pre class=\"example-source\"><code>* Title</code></pre>\n\
+             <div class=\"example-output\">\n<h1>Title</h1>\n</div>\n</div>"
+        ),
+        "{html}"
+    );
+    // Headings inside ranged tags are outside the document outline.
+    assert!(result.toc.is_empty());
+    assert!(result.diagnostics.unwrap_or_default().is_empty());
+}
+
+#[test]
+fn examples_pair_with_their_source_past_hidden_ones() {
+    let result = parse(
+        "#comment\n|example\nhidden\n|end\n\
+         |example\nshown\n|example\ninner\n|end\n|end\n",
+    );
+    let html = result.html_parts.concat();
+    assert!(!html.contains("hidden"), "{html}");
+    assert!(
+        html.contains(
+            "<pre class=\"example-source\"><code>shown\n|example\ninner\n|end</code></pre>"
+        ),
+        "{html}"
+    );
+    assert!(
+        html.contains("<pre class=\"example-source\"><code>inner</code></pre>"),
+        "{html}"
+    );
+    assert!(result.diagnostics.unwrap_or_default().is_empty());
+}
+
+#[test]
+fn callouts_render_configured_kinds() {
+    let html = parse("|warning Mind the gap\nStand *clear*.\n|end\n|note\nPlain.\n|end\n")
+        .html_parts
+        .concat();
+    assert!(
+        html.contains(
+            "<aside class=\"callout callout-warning\" role=\"note\">\n<p class=\"callout-title\">Mind the gap</p>\n<p>Stand <strong>clear</strong>.</p>\n</aside>"
+        ),
+        "{html}"
+    );
+    assert!(
+        html.contains(
+            "<aside class=\"callout callout-note\" role=\"note\">\n<p>Plain.</p>\n</aside>"
+        ),
+        "{html}"
+    );
+
+    let options = ParseOptions {
+        callouts: Some(vec!["aside".into()]),
+        ..Default::default()
+    };
+    let result = parse_with("|aside\nCustom.\n|end\n|note\nGone.\n|end\n", options);
+    let html = result.html_parts.concat();
+    assert!(html.contains("callout-aside"), "{html}");
+    assert!(!html.contains("callout-note"), "{html}");
+    assert_eq!(
+        result.diagnostics.unwrap_or_default(),
+        ["unimplemented ranged tag 'note' — content skipped"]
+    );
+}
+
+#[test]
+fn code_groups_render_as_tab_sets() {
+    let result = parse(
+        "|code-group Install\n@code sh title=\"npm\"\nnpm i\n@end\n@code toml\nx = 1\n@end\nProse.\n|end\n",
+    );
+    let html = result.html_parts.concat();
+    assert!(
+        html.contains(
+            "<div class=\"code-group\"><div role=\"tablist\" aria-label=\"Install\" hidden>\
+             <button type=\"button\" role=\"tab\" id=\"code-group-1-tab-0\" aria-controls=\"code-group-1-panel-0\" aria-selected=\"true\" tabindex=\"0\">npm</button>\
+             <button type=\"button\" role=\"tab\" id=\"code-group-1-tab-1\" aria-controls=\"code-group-1-panel-1\" aria-selected=\"false\" tabindex=\"-1\">toml</button></div>\n\
+             <div role=\"tabpanel\" id=\"code-group-1-panel-0\" aria-labelledby=\"code-group-1-tab-0\">\n\
+             <figure class=\"code-block\">"
+        ),
+        "{html}"
+    );
+    assert_eq!(html.matches("role=\"tabpanel\"").count(), 2, "{html}");
+    assert!(!html.contains("Prose."), "{html}");
+    assert_eq!(
+        result.diagnostics.unwrap_or_default(),
+        ["code-group holds only @code blocks — other content skipped"]
+    );
+}
+
+#[test]
+fn image_options_and_captions() {
+    let html = parse(
+        "@image hero.png width=800 height=450 loading=lazy title=\"Hero shot\"\nA hero\n@end\n",
+    )
+    .html_parts
+    .concat();
+    assert!(
+        html.contains(
+            r#"<img src="./hero.png" alt="A hero" width="800" height="450" loading="lazy" title="Hero shot" />"#
+        ),
+        "{html}"
+    );
+
+    let result = parse(
+        "+caption The *new* {https://example.com}[dashboard]\n+decoding async\n@image dash.png\nDashboard\n@end\n",
+    );
+    let html = result.html_parts.concat();
+    assert!(
+        html.contains(
+            r#"<figure><img src="./dash.png" alt="Dashboard" decoding="async" /><figcaption>The <strong>new</strong> <a href="https://example.com" target="_blank" rel="noopener noreferrer">dashboard</a></figcaption></figure>"#
+        ),
+        "{html}"
+    );
+    assert!(result.diagnostics.unwrap_or_default().is_empty());
+}
+
+#[test]
+fn local_image_dimensions_from_file_path() {
+    let dir = std::env::temp_dir().join(format!("norg-image-size-{}", std::process::id()));
+    fs::create_dir_all(dir.join("img")).unwrap();
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+    png.extend([0, 0, 0x03, 0x20, 0, 0, 0x01, 0xC2]);
+    fs::write(dir.join("img/hero.png"), png).unwrap();
+
+    let options = ParseOptions {
+        file_path: Some(dir.join("page.norg").to_string_lossy().into_owned()),
+        ..Default::default()
+    };
+    let result = parse_with(
+        "@image img/hero.png\nHero\n@end\n@image img/hero.png width=400\nHalf\n@end\n@image img/gone.png\nGone\n@end\n@image https://example.com/remote.png\nRemote\n@end\n",
+        options,
+    );
+    fs::remove_dir_all(&dir).unwrap();
+
+    let html = result.html_parts.concat();
+    assert!(
+        html.contains(r#"<img src="./img/hero.png" alt="Hero" width="800" height="450" />"#),
+        "{html}"
+    );
+    assert!(
+        html.contains(r#"alt="Half" width="400" height="225" />"#),
+        "{html}"
+    );
+    assert!(
+        html.contains(r#"<img src="./img/gone.png" alt="Gone" />"#),
+        "{html}"
+    );
+    assert!(html.contains(r#"alt="Remote" />"#), "{html}");
+    let diagnostics = result.diagnostics.unwrap_or_default();
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert!(diagnostics[0].starts_with("image not found: img/gone.png"));
+}
+
+#[test]
+fn image_sizes_stay_within_the_include_root() {
+    let dir = std::env::temp_dir().join(format!("norg-image-root-{}", std::process::id()));
+    fs::create_dir_all(dir.join("docs/folder.png")).unwrap();
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+    png.extend([0, 0, 0x03, 0x20, 0, 0, 0x01, 0xC2]);
+    fs::write(dir.join("shared.png"), png).unwrap();
+    let file_path = Some(dir.join("docs/page.norg").to_string_lossy().into_owned());
+    let content = "@image ../shared.png\nShared\n@end\n@image folder.png\nFolder\n@end\n";
+
+    let refused = parse_with(
+        content,
+        ParseOptions {
+            file_path: file_path.clone(),
+            ..Default::default()
+        },
+    );
+    let allowed = parse_with(
+        content,
+        ParseOptions {
+            file_path,
+            include_root: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+    );
+    fs::remove_dir_all(&dir).unwrap();
+
+    let html = refused.html_parts.concat();
+    assert!(html.contains(r#"alt="Shared" />"#), "{html}");
+    let diagnostics = refused.diagnostics.unwrap_or_default();
+    assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+    assert!(
+        diagnostics[0].starts_with("not reading the size of image ../shared.png: it is outside "),
+        "{diagnostics:?}"
+    );
+    assert_eq!(
+        diagnostics[1],
+        "not reading the size of image folder.png: not a file"
+    );
+
+    let html = allowed.html_parts.concat();
+    assert!(
+        html.contains(r#"alt="Shared" width="800" height="450" />"#),
+        "{html}"
+    );
+}
+
+#[test]
+fn asset_placeholders_replace_local_file_urls() {
+    let options = ParseOptions {
+        asset_placeholders: Some(true),
+        ..Default::default()
+    };
+    let result = parse_with(
+        "@image img/a.png\nA\n@end\n{:files/report.pdf:}[Report] {:notes.norg:}[Notes] {https://example.com/b.png}[Remote]\n",
+        options,
+    );
+    let html = result.html_parts.concat();
+    assert!(
+        html.contains(r#"<img src="__NORG_ASSET_0__" alt="A" />"#),
+        "{html}"
+    );
+    assert!(
+        html.contains(r#"<a href="__NORG_ASSET_1__">Report</a>"#),
+        "{html}"
+    );
+    assert!(html.contains(r#"href="notes.html""#), "{html}");
+    assert!(
+        html.contains(r#"href="https://example.com/b.png""#),
+        "{html}"
+    );
+
+    let assets: Vec<_> = result
+        .assets
+        .iter()
+        .map(|asset| {
+            (
+                asset.kind,
+                asset.path.as_str(),
+                asset.placeholder.as_str(),
+                asset.part,
+            )
+        })
+        .collect();
+    assert_eq!(
+        assets,
+        [
+            (AssetKind::image, "./img/a.png", "__NORG_ASSET_0__", 0),
+            (AssetKind::link, "./files/report.pdf", "__NORG_ASSET_1__", 0),
+        ]
+    );
+
+    assert!(parse("@image img/a.png\nA\n@end\n").assets.is_empty());
+}
+
+#[test]
+fn image_gallery_renders_a_figure_per_line() {
+    let result = parse(
+        "@image.gallery loading=lazy\n  one.png The first\n\n  https://example.com/two.jpg\n  javascript:alert(1) Bad\n@end\n",
+    );
+    let html = result.html_parts.concat();
+    assert!(
+        html.contains(concat!(
+            r#"<div class="gallery">"#,
+            r#"<figure><img src="./one.png" alt="The first" loading="lazy" /></figure>"#,
+            r#"<figure><img src="https://example.com/two.jpg" alt="" loading="lazy" /></figure>"#,
+            "</div>"
+        )),
+        "{html}"
+    );
+    let diagnostics = result.diagnostics.unwrap_or_default();
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert!(
+        diagnostics[0].starts_with("dropping image"),
+        "{diagnostics:?}"
+    );
+}
+
+#[test]
+fn infirm_image_matches_the_verbatim_form() {
+    let infirm = parse("+loading lazy\n.image shots/a.png width=640\n");
+    let verbatim = parse("+loading lazy\n@image shots/a.png width=640\n@end\n");
+    assert_eq!(infirm.html_parts, verbatim.html_parts);
+    assert!(
+        infirm
+            .html_parts
+            .concat()
+            .contains(r#"<img src="./shots/a.png" alt="" width="640" loading="lazy" />"#),
+        "{:?}",
+        infirm.html_parts
+    );
+    assert!(infirm.diagnostics.unwrap_or_default().is_empty());
+
+    let refused = parse(".image javascript:alert(1)\n");
+    assert!(!refused.html_parts.concat().contains("<img"));
+    assert_eq!(refused.diagnostics.unwrap_or_default().len(), 1);
+
+    let unknown = parse(".toc\n");
+    assert_eq!(
+        unknown.diagnostics.unwrap_or_default(),
+        ["unimplemented infirm tag 'toc' — content skipped"]
+    );
+}
+
+#[test]
+fn video_and_audio_tags() {
+    let result = parse(concat!(
+        "@video demo.mp4 poster=demo.jpg captions=demo.en.vtt lang=en muted loop\n",
+        "A screen recording\n",
+        "@end\n",
+        ".audio https://example.com/talk.mp3 autoplay\n",
+        ".video javascript:alert(1)\n",
+        ".audio talk.ogg poster=cover.png\n",
+    ));
+    let html = result.html_parts.concat();
+    assert!(
+        html.contains(concat!(
+            r#"<video controls src="./demo.mp4" poster="./demo.jpg" muted loop>"#,
+            r#"<track kind="captions" src="./demo.en.vtt" srclang="en" />"#,
+            "A screen recording</video>"
+        )),
+        "{html}"
+    );
+    assert!(
+        html.contains(r#"<audio controls src="https://example.com/talk.mp3" autoplay></audio>"#),
+        "{html}"
+    );
+    assert!(!html.contains("javascript"), "{html}");
+    assert!(
+        html.contains(r#"<audio controls src="./talk.ogg"></audio>"#),
+        "{html}"
+    );
+    assert_eq!(
+        result.diagnostics.unwrap_or_default(),
+        [
+            "dropping video with unsafe URL scheme: javascript:alert(1)",
+            "unknown audio option 'poster=cover.png' — ignored",
+        ]
+    );
+
+    let sanitized = parse_with(
+        ".video clip.webm captions=clip.vtt\n",
+        ParseOptions {
+            sanitize: Some(SanitizeOptions::default()),
+            ..Default::default()
+        },
+    );
+    assert!(
+        sanitized.html_parts.concat().contains(
+            r#"<video controls src="./clip.webm"><track kind="captions" src="./clip.vtt" /></video>"#
+        ),
+        "{:?}",
+        sanitized.html_parts
+    );
+    assert!(sanitized.diagnostics.unwrap_or_default().is_empty());
+
+    let quoted =
+        parse(".video demo.mp4 poster=\"first frame.jpg\" captions=\"demo en.vtt\" muted\n");
+    assert!(
+        quoted.html_parts.concat().contains(concat!(
+            r#"<video controls src="./demo.mp4" poster="./first frame.jpg" muted>"#,
+            r#"<track kind="captions" src="./demo en.vtt" />"#
+        )),
+        "{:?}",
+        quoted.html_parts
+    );
+    assert!(quoted.diagnostics.unwrap_or_default().is_empty());
+}
+
+#[test]
+fn code_line_numbers_and_highlighted_lines() {
+    let code = "a\nb\nc";
+    for lang in ["rust", "no-such-language"] {
+        let result = parse(&format!("@code {lang} {{2-3}} linenos=5\n{code}\n@end\n"));
+        let html = result.html_parts.concat();
+        assert!(html.contains("line-numbers"), "{html}");
+        assert!(
+            html.contains(r#"<span class="line" data-line="5">"#),
+            "{html}"
+        );
+        assert!(
+            html.contains(r#"<span class="line highlighted" data-line="6">"#),
+            "{html}"
+        );
+        assert!(
+            html.contains(r#"<span class="line highlighted" data-line="7">"#),
+            "{html}"
+        );
+        assert!(result.diagnostics.unwrap_or_default().is_empty());
+    }
+
+    let result = parse("@code text {4}\none\n@end\n");
+    assert_eq!(
+        result.diagnostics.unwrap_or_default(),
+        ["highlighted line 4 is past the end of a 1-line code block"]
+    );
+}
+
+#[test]
+fn code_block_captions_and_copy_text() {
+    let html = parse("@code rust file=src/main.rs\nfn main() {}\n@end\n")
+        .html_parts
+        .concat();
+    assert!(
+        html.starts_with(concat!(
+            r#"<figure class="code-block"><figcaption class="code-title"><code>src/main.rs</code></figcaption>"#,
+            r#"<pre class="arborium lang-rust"><code>"#
+        )),
+        "{html}"
+    );
+    assert!(html.trim_end().ends_with("</pre></figure>"), "{html}");
+
+    let options = ParseOptions {
+        copy_code: Some(true),
+        ..Default::default()
+    };
+    let html = parse_with(
+        "@code shell title=\"Install it\"\nnpm i -D vite-plugin-norg && echo <ok>\n@end\n",
+        options,
+    )
+    .html_parts
+    .concat();
+    assert!(
+        html.contains(r#"<figcaption class="code-title">Install it</figcaption>"#),
+        "{html}"
+    );
+    assert!(
+        html.contains(
+            r#" data-code="npm i -D vite-plugin-norg &amp;&amp; echo &lt;ok&gt;"><code>"#
+        ),
+        "{html}"
+    );
+}
+
+#[test]
+fn code_blocks_include_files_from_disk() {
+    let dir = std::env::temp_dir().join(format!("norg-include-{}", std::process::id()));
+    fs::create_dir_all(dir.join("examples")).unwrap();
+    fs::write(
+        dir.join("examples/basic.rs"),
+        "use std::io;\n\nfn main() {\n    // #region setup\n    let x = 1;\n    // #endregion\n}\n",
+    )
+    .unwrap();
+
+    let options = ParseOptions {
+        file_path: Some(dir.join("page.norg").to_string_lossy().into_owned()),
+        ..Default::default()
+    };
+    let result = parse_with(
+        "@code rust file=examples/basic.rs lines=1\n@end\n\
+         @code rust file=examples/basic.rs region=setup\n@end\n\
+         @code rust file=examples/basic.rs lines=9-12\n@end\n\
+         @code rust file=examples/gone.rs\n@end\n\
+         @code rust file=examples\n@end\n",
+        options,
+    );
+    fs::remove_dir_all(&dir).unwrap();
+
+    let html = result.html_parts.concat();
+    assert!(
+        html.contains(
+            r#"<figcaption class="code-title"><code>examples/basic.rs</code></figcaption>"#
+        ),
+        "{html}"
+    );
+    // Line 1 and the setup region, without the region's markers.
+    assert!(html.contains("std") && html.contains("let"), "{html}");
+    assert!(
+        !html.contains("main") && !html.contains("#region"),
+        "{html}"
+    );
+    assert_eq!(
+        result.dependencies,
+        [
+            dir.join("examples/basic.rs").to_string_lossy(),
+            dir.join("examples/gone.rs").to_string_lossy(),
+        ]
+    );
+    let diagnostics = result.diagnostics.unwrap_or_default();
+    assert_eq!(diagnostics.len(), 3, "{diagnostics:?}");
+    assert_eq!(
+        diagnostics[0],
+        "lines 9-12 are past the end of examples/basic.rs (7 lines)"
+    );
+    assert!(
+        diagnostics[1].starts_with("included file not found: examples/gone.rs"),
+        "{diagnostics:?}"
+    );
+    assert_eq!(diagnostics[2], "cannot include examples: not a file");
+
+    let diagnostics = parse("@code rust file=examples/basic.rs\n@end\n")
+        .diagnostics
+        .unwrap_or_default();
+    assert_eq!(
+        diagnostics,
+        ["cannot include examples/basic.rs: the document's file path is unknown (set filePath)"]
+    );
+}
+
+#[test]
+fn includes_stay_within_the_include_root() {
+    let dir = std::env::temp_dir().join(format!("norg-include-root-{}", std::process::id()));
+    fs::create_dir_all(dir.join("docs")).unwrap();
+    fs::write(dir.join("shared.rs"), "fn secret_value() {}\n").unwrap();
+    let file_path = Some(dir.join("docs/page.norg").to_string_lossy().into_owned());
+
+    let relative = "@code rust file=../shared.rs\n@end\n";
+    let absolute = format!(
+        "@code rust file={}\n@end\n",
+        dir.join("shared.rs").display()
+    );
+    let refused = [relative, absolute.as_str()].map(|content| {
+        parse_with(
+            content,
+            ParseOptions {
+                file_path: file_path.clone(),
+                ..Default::default()
+            },
+        )
+    });
+    let allowed = parse_with(
+        relative,
+        ParseOptions {
+            file_path,
+            include_root: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+    );
+    fs::remove_dir_all(&dir).unwrap();
+
+    for result in refused {
+        let html = result.html_parts.concat();
+        assert!(!html.contains("secret_value"), "{html}");
+        assert!(result.dependencies.is_empty());
+        let diagnostics = result.diagnostics.unwrap_or_default();
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert!(
+            diagnostics[0].starts_with("refusing to include ")
+                && diagnostics[0].contains(": it is outside "),
+            "{diagnostics:?}"
+        );
+    }
+    let html = allowed.html_parts.concat();
+    assert!(html.contains("secret_value"), "{html}");
+    assert!(allowed.diagnostics.unwrap_or_default().is_empty());
+}
+
+#[test]
+fn code_blocks_tangle_into_files() {
+    let source = "\
+@document.meta
+tangle: {
+  languages: {
+    lua: init.lua
+  }
+}
+@end
+* Setup
+#tangle src/main.rs
+@code rust
+fn main() {
+    run();
+}
+@end
+@code lua
+print(1)
+@end
+|group
+#tangle src/main.rs
+@code rust
+fn run() {}
+@end
+|end
+#tangle.none
+@code lua
+print(2)
+@end
+#comment
+@code lua
+print(3)
+@end
+#tangle a.rs
+#tangle b.rs
+@code rust
+conflict();
+@end
+#tangle
+@code python
+missing()
+@end
+";
+    let result = tangle_norg(source.to_string()).expect("failed to tangle norg");
+    assert_eq!(result.files.len(), 2, "{:?}", result.files);
+    assert_eq!(
+        result.files["src/main.rs"],
+        "fn main() {\n    run();\n}\n\nfn run() {}\n"
+    );
+    assert_eq!(result.files["init.lua"], "print(1)\n");
+    assert_eq!(
+        result.diagnostics,
+        [
+            "code block #5 (rust) has conflicting tangle targets a.rs, b.rs — skipped",
+            "code block #6 (python) is marked #tangle but has no target — give it a path, or \
+             map its language under tangle: in @document.meta",
+        ]
+    );
+
+    // The renderer passes tangle carryovers over.
+    let diagnostics = parse(source).diagnostics.unwrap_or_default();
+    assert!(
+        diagnostics
+            .iter()
+            .all(|message| !message.contains("tangle")),
+        "{diagnostics:?}"
+    );
+}
+
+#[test]
+fn tangle_targets_stay_in_the_document_directory() {
+    let source = "\
+#tangle src/../lib.rs
+@code rust
+fn kept() {}
+@end
+#tangle /etc/profile
+@code sh
+absolute
+@end
+#tangle src/../../out.rs
+@code rust
+escaping
+@end
+#tangle C:\\out.rs
+@code rust
+drive
+@end
+#tangle lib.rs
+@code rust file=other.rs
+@end
+";
+    let result = tangle_norg(source.to_string()).expect("failed to tangle norg");
+    assert_eq!(result.files.len(), 1, "{:?}", result.files);
+    assert_eq!(result.files["src/../lib.rs"], "fn kept() {}\n");
+    assert_eq!(
+        result.diagnostics,
+        [
+            "code block #2 (sh) targets /etc/profile, outside the document's directory — skipped",
+            "code block #3 (rust) targets src/../../out.rs, outside the document's directory — \
+             skipped",
+            "code block #4 (rust) targets C:\\out.rs, outside the document's directory — skipped",
+            "code block #5 (rust) includes other.rs, which tangling doesn't read — skipped",
+        ]
+    );
+}
+
+#[test]
+fn diff_code_blocks_highlight_the_inner_language() {
+    let result = parse("@code diff rust linenos\n-let a = 1;\n+let a = 2;\n ok();\n@end\n");
+    let html = result.html_parts.concat();
+    assert!(
+        html.contains(r#"<pre class="arborium lang-rust line-numbers diff">"#),
+        "{html}"
+    );
+    assert!(
+        html.contains(r#"<span class="line line-removed" data-line="1">"#),
+        "{html}"
+    );
+    assert!(
+        html.contains(r#"<span class="line line-added" data-line="2">"#),
+        "{html}"
+    );
+    assert!(
+        html.contains(r#"<span class="line" data-line="3">"#),
+        "{html}"
+    );
+    assert!(!html.contains('+'), "{html}");
+    assert!(result.diagnostics.unwrap_or_default().is_empty());
+
+    let plain = parse("@code diff\n+added\n@end\n").html_parts.concat();
+    assert!(plain.contains("lang-diff"), "{plain}");
+    assert!(!plain.contains("line-added"), "{plain}");
+}
@@ -73,3 +73,43 @@ it('retries an in-flight read failure invalidated by HMR', async () => {
   expect(result?.code).toContain('Fresh');
   expect(readFile).toHaveBeenCalledTimes(2);
 });
+
+it('reparses a document when a file it includes changes', async () => {
+  readFile.mockResolvedValue('@code rust file=snippet.rs\n@end\n');
+
+  const file = '/tmp/cache-include.norg';
+  const plugin = norgPlugin({
+    mode: 'html',
+    include: ['**/*.norg'],
+    readFiles: true,
+  });
+  const context = {
+    warn: vi.fn(),
+    addWatchFile: vi.fn(),
+    error(message: string): never {
+      throw new Error(message);
+    },
+  };
+  const load = plugin.load as (
+    this: typeof context,
+    id: string
+  ) => Promise<{ code: string } | undefined>;
+  await load.call(context, file);
+  expect(context.addWatchFile).toHaveBeenCalledWith('/tmp/snippet.rs');
+
+  const hotUpdate = plugin.handleHotUpdate as (ctx: {
+    file: string;
+    modules: never[];
+  }) => Promise<unknown>;
+  await hotUpdate({ file: '/tmp/snippet.rs', modules: [] });
+  await load.call(context, file);
+  expect(readFile).toHaveBeenCalledTimes(2);
+
+  // Once the include is removed, the file no longer re-renders the document.
+  readFile.mockResolvedValue('* No includes\n');
+  await hotUpdate({ file, modules: [] });
+  await load.call(context, file);
+  await hotUpdate({ file: '/tmp/snippet.rs', modules: [] });
+  await load.call(context, file);
+  expect(readFile).toHaveBeenCalledTimes(3);
+});
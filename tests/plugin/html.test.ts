@@ -32,6 +32,32 @@ describe('HTML Generator', () => {
     });
   });
 
+  it('imports local assets in place of their placeholders', async () => {
+    const assetPlugin = norgPlugin({ mode: 'html', parser: { assetPlaceholders: true } });
+    const code = await loadCode(assetPlugin, join(fixturesDir, 'images.norg'));
+
+    expect(code).toContain(
+      `import __norgAsset0 from '${join(fixturesDir, 'relative/image.png')}';`
+    );
+    expect(code).toContain('<img src=\\"" + __norgAsset0 + "\\" alt=');
+    expect(code).not.toContain('__NORG_ASSET_');
+  });
+
+  it('opens collapsed sections around linked headings', async () => {
+    const collapsing = norgPlugin({ mode: 'html', parser: { collapseLevel: 2 } });
+    const headings = join(fixturesDir, 'headings.norg');
+    const code = await loadCode(collapsing, headings);
+
+    expect(code).toContain('<details><summary><h2');
+    expect(code).toContain('import "virtual:norg-sections";');
+    expect(await loadCode(plugin, headings)).not.toContain('virtual:norg-sections');
+
+    const resolved = collapsing.resolveId('virtual:norg-sections') as string;
+    const script = await loadCode(collapsing, resolved);
+    expect(script).toContain("addEventListener('hashchange'");
+    expect(script).toContain('details.open = true');
+  });
+
   it.each(fixtures)('generates correct output for %s', async fixture => {
     const fixturePath = join(fixturesDir, fixture);
     const code = await loadCode(plugin, fixturePath);
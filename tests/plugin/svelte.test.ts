@@ -5,6 +5,15 @@ import { fixturesDir, fixtures, loadCode } from './fixtures';
 describe('Svelte Generator', () => {
   const plugin = norgPlugin({ mode: 'svelte', include: ['**/*.norg'] });
 
+  it('imports local assets in an instance script', async () => {
+    const assetPlugin = norgPlugin({ mode: 'svelte', parser: { assetPlaceholders: true } });
+    const code = await loadCode(assetPlugin, join(fixturesDir, 'images.norg'));
+
+    expect(code).toMatch(/^<script>\nimport __norgAsset0 from '[^']+relative\/image\.png';/);
+    expect(code).toContain('{@html "<h1');
+    expect(code).toContain('" + __norgAsset0 + "');
+  });
+
   it.each(fixtures)('generates correct output for %s', async fixture => {
     const fixturePath = join(fixturesDir, fixture);
     const code = await loadCode(plugin, fixturePath);